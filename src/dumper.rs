@@ -0,0 +1,122 @@
+use crate::parser::{Child, ModelicaCST, Position, TokenID, TreeID};
+
+impl ModelicaCST {
+    /// Return the tree in the form of an indented S-expression.
+    ///
+    /// Every node is printed as a list that starts with its kind,
+    /// followed by its children. Tokens are printed as quoted texts.
+    /// Nodes that contain only tokens are printed in a single line.
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        if let Some(root) = self.root() {
+            sexpr(self, root, 0, &mut out);
+        }
+        out
+    }
+
+    /// Return the tree in the JSON format.
+    ///
+    /// Nodes are represented by objects with `kind`, `start`, `end` and
+    /// `children` fields, and tokens by objects with `token`, `text`,
+    /// `start` and `end` fields. Positions are objects with `line`,
+    /// `col` and `offset` fields.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        match self.root() {
+            Some(root) if !self.is_empty(root) => json(self, root, 0, &mut out),
+            _ => out += "null",
+        }
+        out
+    }
+}
+
+fn sexpr(cst: &ModelicaCST, tree: TreeID, level: usize, out: &mut String) {
+    out.push('(');
+    out.push_str(&format!("{:?}", cst.kind(tree)));
+    let inline = cst
+        .children(tree)
+        .iter()
+        .all(|c| matches!(c, Child::Token(_)));
+    for child in cst.children(tree) {
+        if inline {
+            out.push(' ');
+        } else {
+            out.push('\n');
+            out.push_str(&"  ".repeat(level + 1));
+        }
+        match child {
+            Child::Token(tok) => {
+                out.push('\'');
+                for c in cst.tokens().text(*tok).chars() {
+                    if c == '\'' || c == '\\' {
+                        out.push('\\');
+                    }
+                    out.push(c);
+                }
+                out.push('\'');
+            }
+            Child::Tree(t) => sexpr(cst, *t, level + 1, out),
+        }
+    }
+    out.push(')');
+}
+
+fn json(cst: &ModelicaCST, tree: TreeID, level: usize, out: &mut String) {
+    let indent = "  ".repeat(level);
+    let tokens = cst.tokens();
+    out.push_str("{\n");
+    out.push_str(&format!(
+        "{indent}  \"kind\": \"{:?}\",\n{indent}  \"start\": {},\n{indent}  \"end\": {},\n",
+        cst.kind(tree),
+        position(tokens.start(cst.start(tree))),
+        position(tokens.end(cst.end(tree))),
+    ));
+    out.push_str(&format!("{indent}  \"children\": ["));
+    for (i, child) in cst.children(tree).iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&format!("\n{indent}    "));
+        match child {
+            Child::Token(tok) => token(cst, *tok, out),
+            Child::Tree(t) => json(cst, *t, level + 2, out),
+        }
+    }
+    out.push_str(&format!("\n{indent}  ]\n{indent}}}"));
+}
+
+fn token(cst: &ModelicaCST, tok: TokenID, out: &mut String) {
+    let tokens = cst.tokens();
+    out.push_str(&format!(
+        "{{\"token\": {}, \"text\": {}, \"start\": {}, \"end\": {}}}",
+        string(&format!("{:?}", tokens.kind(tok))),
+        string(tokens.text(tok)),
+        position(tokens.start(tok)),
+        position(tokens.end(tok)),
+    ));
+}
+
+fn position(pos: Position) -> String {
+    format!(
+        "{{\"line\": {}, \"col\": {}, \"offset\": {}}}",
+        pos.line, pos.col, pos.offset
+    )
+}
+
+/// Return the text as a JSON string literal
+fn string(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,195 @@
+use std::ops::Range;
+
+use crate::formatter::FormatOptions;
+use crate::parser::{ModelicaCST, TokenID, Tokens};
+
+/// Represents a replacement of a span of tokens with a new text.
+///
+/// # Fields
+/// - `start`: first token of the replaced span
+/// - `end`: last token of the replaced span
+/// - `text`: text inserted in place of the span
+pub struct TextEdit {
+    pub start: TokenID,
+    pub end: TokenID,
+    pub text: String,
+}
+
+impl TextEdit {
+    /// Return an edit that removes the span of tokens
+    pub fn delete(start: TokenID, end: TokenID) -> Self {
+        TextEdit {
+            start,
+            end,
+            text: String::new(),
+        }
+    }
+
+    /// Return an edit that replaces the span of tokens with the text
+    pub fn replace(start: TokenID, end: TokenID, text: String) -> Self {
+        TextEdit { start, end, text }
+    }
+
+    /// Return the byte range affected by the edit. Removal of the
+    /// entire line also removes its indentation and line break.
+    fn range(&self, tokens: &Tokens) -> Range<usize> {
+        let code = tokens.code();
+        let mut start = tokens.range(self.start).start;
+        let mut end = tokens.range(self.end).end;
+        if self.text.is_empty() {
+            let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = code[end..].find('\n').map_or(code.len(), |i| end + i + 1);
+            if code[line_start..start].trim().is_empty() && code[end..line_end].trim().is_empty() {
+                start = line_start;
+                end = line_end;
+            }
+        }
+        start..end
+    }
+}
+
+/// Represents an automatic fix. Edits constituting the fix are always
+/// applied together.
+pub struct Fix {
+    pub edits: Vec<TextEdit>,
+}
+
+impl ModelicaCST {
+    /// Apply fixes to the source code and return the result formatted
+    /// with the given options, together with the number of applied
+    /// fixes.
+    ///
+    /// Fixes that overlap with previously accepted ones are skipped, as
+    /// well as fixes that would introduce syntax errors.
+    pub fn apply_fixes(&self, fixes: &[&Fix], options: &FormatOptions) -> (String, usize) {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut accepted = Vec::new();
+        for fix in fixes {
+            let fix_ranges: Vec<Range<usize>> =
+                fix.edits.iter().map(|e| e.range(self.tokens())).collect();
+            let overlaps = fix_ranges
+                .iter()
+                .any(|r| ranges.iter().any(|a| r.start < a.end && a.start < r.end));
+            if !overlaps {
+                ranges.extend(fix_ranges);
+                accepted.push(*fix);
+            }
+        }
+        let mut applied = Vec::new();
+        let fixed = match self.reparse(&accepted) {
+            Some(cst) => {
+                applied = accepted;
+                Some(cst)
+            }
+            None => {
+                // Some of the fixes break the code, so try them one by one
+                let mut fixed = None;
+                for fix in accepted {
+                    applied.push(fix);
+                    match self.reparse(&applied) {
+                        Some(cst) => fixed = Some(cst),
+                        None => _ = applied.pop(),
+                    }
+                }
+                fixed
+            }
+        };
+        match fixed {
+            Some(cst) => (cst.pretty_print_with(options), applied.len()),
+            None => (self.pretty_print_with(options), 0),
+        }
+    }
+
+    /// Apply fixes and parse the result. Return `None` if fixed code
+    /// contains errors.
+    fn reparse(&self, fixes: &[&Fix]) -> Option<ModelicaCST> {
+        let mut edits: Vec<(Range<usize>, &str)> = fixes
+            .iter()
+            .flat_map(|f| f.edits.iter())
+            .map(|e| (e.range(self.tokens()), e.text.as_str()))
+            .collect();
+        edits.sort_by_key(|(r, _)| r.start);
+        let mut code = String::from(self.tokens().code());
+        for (range, text) in edits.into_iter().rev() {
+            code.replace_range(range, text);
+        }
+        let cst = ModelicaCST::from(String::from(self.tokens().source()), code, self.entry());
+        if cst.tokens().errors().is_empty() && cst.errors().is_empty() {
+            Some(cst)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::EntryPoint;
+
+    fn parse(code: &str) -> ModelicaCST {
+        ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        )
+    }
+
+    #[test]
+    fn test_apply_lint_fixes() {
+        let cst = parse("model Foo\n  import A.B;\n  import C.D;\n  Real x = D.y;\nend Foo;");
+        let diagnostics = cst.lint();
+        let fixes: Vec<&Fix> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+        let (code, applied) = cst.apply_fixes(&fixes, &FormatOptions::new());
+        assert_eq!(applied, 1);
+        assert_eq!(
+            code,
+            "model Foo\n\n  import C.D;\n  Real x = D.y;\n\nend Foo;\n"
+        );
+        // Fixed code is formatted with the given options
+        let mut options = FormatOptions::new();
+        options.blank_lines_in_composition = false;
+        let (code, _) = cst.apply_fixes(&fixes, &options);
+        assert_eq!(code, "model Foo\n  import C.D;\n  Real x = D.y;\nend Foo;\n");
+    }
+
+    #[test]
+    fn test_skip_overlapping_fixes() {
+        let cst = parse("model Foo\n  Real x;\nend Foo;");
+        let tokens = cst.tokens().tokens();
+        // Replace `Real` and then `Real x`
+        let first = Fix {
+            edits: vec![TextEdit::replace(
+                tokens[2],
+                tokens[2],
+                String::from("Integer"),
+            )],
+        };
+        let second = Fix {
+            edits: vec![TextEdit::replace(
+                tokens[2],
+                tokens[3],
+                String::from("Boolean y"),
+            )],
+        };
+        let (code, applied) = cst.apply_fixes(&[&first, &second], &FormatOptions::new());
+        assert_eq!(applied, 1);
+        assert_eq!(code, "model Foo\n\n  Integer x;\n\nend Foo;\n");
+    }
+
+    #[test]
+    fn test_reject_breaking_fixes() {
+        let cst = parse("model Foo\n  Real x;\nend Foo;");
+        let tokens = cst.tokens().tokens();
+        // Remove the semicolon and rename the component
+        let breaking = Fix {
+            edits: vec![TextEdit::delete(tokens[4], tokens[4])],
+        };
+        let correct = Fix {
+            edits: vec![TextEdit::replace(tokens[3], tokens[3], String::from("y"))],
+        };
+        let (code, applied) = cst.apply_fixes(&[&breaking, &correct], &FormatOptions::new());
+        assert_eq!(applied, 1);
+        assert_eq!(code, "model Foo\n\n  Real y;\n\nend Foo;\n");
+    }
+}
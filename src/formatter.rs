@@ -1,12 +1,122 @@
 mod formatting;
+mod html;
 mod printing;
 
-use crate::parser::ModelicaCST;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::parser::{parse, ModelicaCST, ParseErrors};
+
+#[derive(Debug, Clone)]
+/// Represents options that control formatting.
+pub struct FormatOptions {
+    /// Reformat HTML inside `info` and `revisions` strings of the
+    /// `Documentation` annotation
+    pub html: bool,
+    /// Align columns of matrices that are written in multiple lines.
+    /// Widths are measured in characters, so columns containing wide
+    /// or combining characters may still look misaligned.
+    pub align_matrices: bool,
+    /// Normalize spelling of real literals: use lowercase exponent
+    /// marker, drop the `+` sign of the exponent and add zero after the
+    /// trailing decimal point
+    pub normalize_numbers: bool,
+    /// Maximum number of consecutive blank lines preserved from the
+    /// original code
+    pub max_blank_lines: usize,
+    /// Insert blank lines before sections (like `equation` or
+    /// `protected`) and before their first elements
+    pub blank_lines_around_sections: bool,
+    /// Insert blank lines after the class header and before the `end`
+    /// clause of the class
+    pub blank_lines_in_composition: bool,
+    /// Insert blank lines before nested class definitions
+    pub blank_line_before_classes: bool,
+    /// Normalize comments: insert a space after `//`, strip trailing
+    /// whitespace and shift continuation lines of block comments along
+    /// with their first lines
+    pub normalize_comments: bool,
+    /// Wrap line comments that start a line and exceed the given width.
+    /// Applies only together with `normalize_comments`, and comments
+    /// without letters or digits, like dividers, are never wrapped.
+    pub comment_width: Option<usize>,
+    /// Remove the byte order mark from the beginning of the code instead
+    /// of preserving it
+    pub remove_bom: bool,
+}
+
+impl FormatOptions {
+    /// Return default formatting options
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            html: false,
+            align_matrices: false,
+            normalize_numbers: false,
+            max_blank_lines: 1,
+            blank_lines_around_sections: true,
+            blank_lines_in_composition: true,
+            blank_line_before_classes: false,
+            normalize_comments: false,
+            comment_width: None,
+            remove_bom: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents a reason why the code could not be formatted.
+pub enum FormatError {
+    /// Code contains lexical or syntax errors
+    Parse(ParseErrors),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            FormatError::Parse(errors) => write!(f, "syntax errors detected\n{}", errors),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Parse(errors) => Some(errors),
+        }
+    }
+}
+
+impl From<ParseErrors> for FormatError {
+    fn from(errors: ParseErrors) -> Self {
+        FormatError::Parse(errors)
+    }
+}
 
 impl ModelicaCST {
     /// Return string containing formatted Modelica code represented by the CST.
     pub fn pretty_print(&self) -> String {
-        let markers = formatting::format(self);
-        printing::print(self, markers)
+        self.pretty_print_with(&FormatOptions::default())
+    }
+
+    /// Return string containing Modelica code represented by the CST
+    /// formatted with the specified options.
+    pub fn pretty_print_with(&self, options: &FormatOptions) -> String {
+        let markers = formatting::format(self, options);
+        printing::print(self, markers, options)
     }
 }
+
+/// Format Modelica source code.
+///
+/// Return formatted code, or `FormatError` if the code can't be
+/// formatted. Errors refer to the code as `<input>`.
+pub fn format_str(source: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    let cst = parse(source, "<input>")?;
+    Ok(cst.pretty_print_with(options))
+}
@@ -1,14 +1,17 @@
-use std::{iter::Peekable, vec::IntoIter};
-
 use crate::parser::*;
 
+use super::{html, printing, FormatOptions};
+
 #[derive(PartialEq)]
 pub enum Marker {
     Token(TokenID),
+    /// Token which text was modified by the formatter
+    Text(String),
     Indent,
     Dedent,
     Space,
-    Blank,
+    /// Line break followed by the given number of blank lines
+    Blank(usize),
     Break,
 }
 
@@ -20,138 +23,157 @@ enum Blank {
 }
 
 /// Return collection of markers that should be consumed to generate pretty printed string
-pub fn format(cst: &ModelicaCST) -> Vec<Marker> {
-    let mut f = Formatter::new(cst);
-    match cst.kind(cst.root().unwrap()) {
-        SyntaxKind::StoredDefinition => stored_definition(&mut f, cst.root().unwrap()),
-        SyntaxKind::ClassDefinition => class_definition(&mut f, cst.root().unwrap()),
-        SyntaxKind::ClassPrefixes => class_prefixes(&mut f, cst.root().unwrap()),
-        SyntaxKind::ClassSpecifier => class_specifier(&mut f, cst.root().unwrap()),
-        SyntaxKind::LongClassSpecifier => long_class_specifier(&mut f, cst.root().unwrap()),
-        SyntaxKind::ShortClassSpecifier => short_class_specifier(&mut f, cst.root().unwrap()),
-        SyntaxKind::DerClassSpecifier => der_class_specifier(&mut f, cst.root().unwrap()),
-        SyntaxKind::BasePrefix => base_prefix(&mut f, cst.root().unwrap()),
-        SyntaxKind::EnumList => enum_list(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::EnumerationLiteral => enumeration_literal(&mut f, cst.root().unwrap()),
-        SyntaxKind::Composition => composition(&mut f, cst.root().unwrap()),
-        SyntaxKind::LanguageSpecification => language_specification(&mut f, cst.root().unwrap()),
-        SyntaxKind::ExternalFunctionCall => external_function_call(&mut f, cst.root().unwrap()),
-        SyntaxKind::ElementList => element_list(&mut f, cst.root().unwrap()),
-        SyntaxKind::Element => element(&mut f, cst.root().unwrap()),
-        SyntaxKind::ImportClause => import_clause(&mut f, cst.root().unwrap()),
-        SyntaxKind::ImportList => import_list(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::ExtendsClause => extends_clause(&mut f, cst.root().unwrap()),
-        SyntaxKind::ConstrainingClause => constraining_clause(&mut f, cst.root().unwrap()),
+pub fn format(cst: &ModelicaCST, options: &FormatOptions) -> Vec<Marker> {
+    let mut f = Formatter::new(cst, options);
+    let root = match cst.root() {
+        Some(root) if !cst.is_empty(root) => root,
+        // Input contains only trivia
+        _ => {
+            f.flush_comments();
+            return f.markers;
+        }
+    };
+    f.leading_comments(cst.start(root));
+    match cst.kind(root) {
+        SyntaxKind::StoredDefinition => stored_definition(&mut f, root),
+        SyntaxKind::ClassDefinition => class_definition(&mut f, root),
+        SyntaxKind::ClassPrefixes => class_prefixes(&mut f, root),
+        SyntaxKind::ClassSpecifier => class_specifier(&mut f, root),
+        SyntaxKind::LongClassSpecifier => long_class_specifier(&mut f, root),
+        SyntaxKind::ShortClassSpecifier => short_class_specifier(&mut f, root),
+        SyntaxKind::DerClassSpecifier => der_class_specifier(&mut f, root),
+        SyntaxKind::BasePrefix => base_prefix(&mut f, root),
+        SyntaxKind::EnumList => enum_list(&mut f, root, false),
+        SyntaxKind::EnumerationLiteral => enumeration_literal(&mut f, root),
+        SyntaxKind::Composition => composition(&mut f, root),
+        SyntaxKind::LanguageSpecification => language_specification(&mut f, root),
+        SyntaxKind::ExternalFunctionCall => external_function_call(&mut f, root),
+        SyntaxKind::ElementList => element_list(&mut f, root),
+        SyntaxKind::Element => element(&mut f, root),
+        SyntaxKind::ImportClause => import_clause(&mut f, root),
+        SyntaxKind::ImportList => import_list(&mut f, root, false),
+        SyntaxKind::ExtendsClause => extends_clause(&mut f, root),
+        SyntaxKind::ConstrainingClause => constraining_clause(&mut f, root),
         SyntaxKind::ClassOrInheritanceModification => {
-            class_or_inheritance_modification(&mut f, cst.root().unwrap())
+            class_or_inheritance_modification(&mut f, root)
         }
         SyntaxKind::ArgumentOrInheritanceModificationList => {
-            argument_or_inheritance_modification_list(&mut f, cst.root().unwrap(), false)
+            argument_or_inheritance_modification_list(&mut f, root, false)
         }
         SyntaxKind::InheritanceModification => {
-            inheritance_modification(&mut f, cst.root().unwrap())
-        }
-        SyntaxKind::ComponentClause => component_clause(&mut f, cst.root().unwrap()),
-        SyntaxKind::TypePrefix => type_prefix(&mut f, cst.root().unwrap()),
-        SyntaxKind::ComponentList => component_list(&mut f, cst.root().unwrap()),
-        SyntaxKind::ComponentDeclaration => component_declaration(&mut f, cst.root().unwrap()),
-        SyntaxKind::ConditionAttribute => condition_attribute(&mut f, cst.root().unwrap()),
-        SyntaxKind::Declaration => declaration(&mut f, cst.root().unwrap()),
-        SyntaxKind::Modification => modification(&mut f, cst.root().unwrap()),
-        SyntaxKind::ModificationExpression => modification_expression(&mut f, cst.root().unwrap()),
-        SyntaxKind::ClassModification => class_modification(&mut f, cst.root().unwrap()),
-        SyntaxKind::ArgumentList => argument_list(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::Argument => argument(&mut f, cst.root().unwrap()),
+            inheritance_modification(&mut f, root)
+        }
+        SyntaxKind::ComponentClause => component_clause(&mut f, root),
+        SyntaxKind::TypePrefix => type_prefix(&mut f, root),
+        SyntaxKind::ComponentList => component_list(&mut f, root),
+        SyntaxKind::ComponentDeclaration => component_declaration(&mut f, root),
+        SyntaxKind::ConditionAttribute => condition_attribute(&mut f, root),
+        SyntaxKind::Declaration => declaration(&mut f, root),
+        SyntaxKind::Modification => modification(&mut f, root),
+        SyntaxKind::ModificationExpression => modification_expression(&mut f, root),
+        SyntaxKind::ClassModification => class_modification(&mut f, root),
+        SyntaxKind::ArgumentList => argument_list(&mut f, root, false),
+        SyntaxKind::Argument => argument(&mut f, root),
         SyntaxKind::ElementModificationOrReplaceable => {
-            element_modification_or_replaceable(&mut f, cst.root().unwrap())
-        }
-        SyntaxKind::ElementModification => element_modification(&mut f, cst.root().unwrap()),
-        SyntaxKind::ElementRedeclaration => element_redeclaration(&mut f, cst.root().unwrap()),
-        SyntaxKind::ElementReplaceable => element_replaceable(&mut f, cst.root().unwrap()),
-        SyntaxKind::ComponentClause1 => component_clause1(&mut f, cst.root().unwrap()),
-        SyntaxKind::ComponentDeclaration1 => component_declaration1(&mut f, cst.root().unwrap()),
-        SyntaxKind::ShortClassDefinition => short_class_definition(&mut f, cst.root().unwrap()),
-        SyntaxKind::EquationSection => equation_section(&mut f, cst.root().unwrap()),
-        SyntaxKind::AlgorithmSection => algorithm_section(&mut f, cst.root().unwrap()),
-        SyntaxKind::Equation => equation(&mut f, cst.root().unwrap()),
-        SyntaxKind::Statement => statement(&mut f, cst.root().unwrap()),
-        SyntaxKind::IfEquation => if_equation(&mut f, cst.root().unwrap()),
-        SyntaxKind::IfStatement => if_statement(&mut f, cst.root().unwrap()),
-        SyntaxKind::ForEquation => for_equation(&mut f, cst.root().unwrap()),
-        SyntaxKind::ForStatement => for_statement(&mut f, cst.root().unwrap()),
-        SyntaxKind::ForIndices => for_indices(&mut f, cst.root().unwrap()),
-        SyntaxKind::ForIndex => for_index(&mut f, cst.root().unwrap()),
-        SyntaxKind::WhileStatement => while_statement(&mut f, cst.root().unwrap()),
-        SyntaxKind::WhenEquation => when_equation(&mut f, cst.root().unwrap()),
-        SyntaxKind::WhenStatement => when_statement(&mut f, cst.root().unwrap()),
-        SyntaxKind::ConnectEquation => connect_equation(&mut f, cst.root().unwrap()),
-        SyntaxKind::Expression => _ = expression(&mut f, cst.root().unwrap(), false, false),
-        SyntaxKind::SimpleExpression => _ = simple_expression(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::LogicalExpression => _ = logical_expression(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::LogicalTerm => _ = logical_term(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::LogicalFactor => _ = logical_factor(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::Relation => _ = relation(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::RelationalOperator => relational_operator(&mut f, cst.root().unwrap()),
+            element_modification_or_replaceable(&mut f, root)
+        }
+        SyntaxKind::ElementModification => element_modification(&mut f, root),
+        SyntaxKind::ElementRedeclaration => element_redeclaration(&mut f, root),
+        SyntaxKind::ElementReplaceable => element_replaceable(&mut f, root),
+        SyntaxKind::ComponentClause1 => component_clause1(&mut f, root),
+        SyntaxKind::ComponentDeclaration1 => component_declaration1(&mut f, root),
+        SyntaxKind::ShortClassDefinition => short_class_definition(&mut f, root),
+        SyntaxKind::EquationSection => equation_section(&mut f, root),
+        SyntaxKind::AlgorithmSection => algorithm_section(&mut f, root),
+        SyntaxKind::Equation => equation(&mut f, root),
+        SyntaxKind::Statement => statement(&mut f, root),
+        SyntaxKind::IfEquation => if_equation(&mut f, root),
+        SyntaxKind::IfStatement => if_statement(&mut f, root),
+        SyntaxKind::ForEquation => for_equation(&mut f, root),
+        SyntaxKind::ForStatement => for_statement(&mut f, root),
+        SyntaxKind::ForIndices => for_indices(&mut f, root),
+        SyntaxKind::ForIndex => for_index(&mut f, root),
+        SyntaxKind::WhileStatement => while_statement(&mut f, root),
+        SyntaxKind::WhenEquation => when_equation(&mut f, root),
+        SyntaxKind::WhenStatement => when_statement(&mut f, root),
+        SyntaxKind::ConnectEquation => connect_equation(&mut f, root),
+        SyntaxKind::Expression => _ = expression(&mut f, root, false, false),
+        SyntaxKind::SimpleExpression => _ = simple_expression(&mut f, root, false),
+        SyntaxKind::LogicalExpression => _ = logical_expression(&mut f, root, false),
+        SyntaxKind::LogicalTerm => _ = logical_term(&mut f, root, false),
+        SyntaxKind::LogicalFactor => _ = logical_factor(&mut f, root, false),
+        SyntaxKind::Relation => _ = relation(&mut f, root, false),
+        SyntaxKind::RelationalOperator => relational_operator(&mut f, root),
         SyntaxKind::ArithmeticExpression => {
-            _ = arithmetic_expression(&mut f, cst.root().unwrap(), false)
-        }
-        SyntaxKind::AddOperator => add_operator(&mut f, cst.root().unwrap()),
-        SyntaxKind::Term => _ = term(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::MulOperator => mul_operator(&mut f, cst.root().unwrap()),
-        SyntaxKind::Factor => _ = factor(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::Primary => _ = primary(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::TypeSpecifier => type_specifier(&mut f, cst.root().unwrap()),
-        SyntaxKind::Name => name(&mut f, cst.root().unwrap()),
-        SyntaxKind::ComponentReference => component_reference(&mut f, cst.root().unwrap()),
-        SyntaxKind::ResultReference => result_reference(&mut f, cst.root().unwrap()),
-        SyntaxKind::FunctionCallArgs => function_call_args(&mut f, cst.root().unwrap()),
-        SyntaxKind::FunctionArguments => function_arguments(&mut f, cst.root().unwrap(), false),
+            _ = arithmetic_expression(&mut f, root, false)
+        }
+        SyntaxKind::AddOperator => add_operator(&mut f, root),
+        SyntaxKind::Term => _ = term(&mut f, root, false),
+        SyntaxKind::MulOperator => mul_operator(&mut f, root),
+        SyntaxKind::Factor => _ = factor(&mut f, root, false),
+        SyntaxKind::Primary => _ = primary(&mut f, root, false),
+        SyntaxKind::TypeSpecifier => type_specifier(&mut f, root),
+        SyntaxKind::Name => name(&mut f, root),
+        SyntaxKind::ComponentReference => component_reference(&mut f, root),
+        SyntaxKind::ResultReference => result_reference(&mut f, root),
+        SyntaxKind::FunctionCallArgs => function_call_args(&mut f, root),
+        SyntaxKind::FunctionArguments => function_arguments(&mut f, root, false),
         SyntaxKind::FunctionArgumentsNonFirst => {
-            function_arguments_non_first(&mut f, cst.root().unwrap(), false)
+            function_arguments_non_first(&mut f, root, false)
         }
-        SyntaxKind::ArrayArguments => array_arguments(&mut f, cst.root().unwrap(), false),
+        SyntaxKind::ArrayArguments => array_arguments(&mut f, root, false),
         SyntaxKind::ArrayArgumentsNonFirst => {
-            array_arguments_non_first(&mut f, cst.root().unwrap(), false)
+            array_arguments_non_first(&mut f, root, false)
         }
-        SyntaxKind::NamedArguments => named_arguments(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::NamedArgument => named_argument(&mut f, cst.root().unwrap()),
-        SyntaxKind::FunctionArgument => function_argument(&mut f, cst.root().unwrap()),
+        SyntaxKind::NamedArguments => named_arguments(&mut f, root, false),
+        SyntaxKind::NamedArgument => named_argument(&mut f, root),
+        SyntaxKind::FunctionArgument => function_argument(&mut f, root),
         SyntaxKind::FunctionPartialApplication => {
-            function_partial_application(&mut f, cst.root().unwrap())
+            function_partial_application(&mut f, root)
         }
         SyntaxKind::OutputExpressionList => {
-            _ = output_expression_list(&mut f, cst.root().unwrap(), false)
-        }
-        SyntaxKind::ExpressionList => expression_list(&mut f, cst.root().unwrap(), false),
-        SyntaxKind::ArraySubscripts => array_subscripts(&mut f, cst.root().unwrap()),
-        SyntaxKind::Subscript => subscript(&mut f, cst.root().unwrap()),
-        SyntaxKind::Description => description(&mut f, cst.root().unwrap()),
-        SyntaxKind::DescriptionString => description_string(&mut f, cst.root().unwrap()),
-        SyntaxKind::AnnotationClause => annotation_clause(&mut f, cst.root().unwrap()),
+            _ = output_expression_list(&mut f, root, false)
+        }
+        SyntaxKind::ExpressionList => _ = expression_list(&mut f, root, false),
+        SyntaxKind::ArraySubscripts => array_subscripts(&mut f, root),
+        SyntaxKind::Subscript => subscript(&mut f, root),
+        SyntaxKind::Description => description(&mut f, root),
+        SyntaxKind::DescriptionString => description_string(&mut f, root),
+        SyntaxKind::AnnotationClause => annotation_clause(&mut f, root),
+        #[cfg(feature = "optimica")]
+        SyntaxKind::ConstraintSection => constraint_section(&mut f, root),
+        #[cfg(feature = "metamodelica")]
+        SyntaxKind::MatchExpression => match_expression(&mut f, root),
+        #[cfg(feature = "metamodelica")]
+        SyntaxKind::MatchCase => match_case(&mut f, root),
+        SyntaxKind::Script => script(&mut f, root),
         SyntaxKind::Error => (),
     }
+    f.flush_comments();
     f.markers
 }
 
 /// Helper structure that collects markers
 struct Formatter<'a> {
     cst: &'a ModelicaCST,
+    options: &'a FormatOptions,
     markers: Vec<Marker>,
-    comments: Peekable<IntoIter<TokenID>>,
+    prev_tok: Option<TokenID>,
+    handled: Option<TokenID>,
     prev_kind: TokenKind,
     prev_line: usize,
-    prev_tok: TokenID,
 }
 
 impl<'a> Formatter<'a> {
-    fn new(cst: &'a ModelicaCST) -> Self {
+    fn new(cst: &'a ModelicaCST, options: &'a FormatOptions) -> Self {
         Formatter {
             cst,
+            options,
             markers: Vec::new(),
-            comments: cst.tokens().comments().into_iter().peekable(),
+            prev_tok: None,
+            handled: None,
             prev_kind: TokenKind::Eof,
             prev_line: 1,
-            prev_tok: cst.tokens().first(),
         }
     }
 
@@ -169,80 +191,151 @@ impl<'a> Formatter<'a> {
         let (inlines, comments) = self.comments_before(tok);
         for comment in inlines {
             if !self.markers.is_empty() {
-                // Keep the distance between the last token on the line and the following inline comment
-                let distance =
-                    self.cst.tokens().start(comment).col - self.cst.tokens().end(self.prev_tok).col;
-                for _ in 0..distance {
-                    self.markers.push(Marker::Space);
-                }
+                self.markers.push(Marker::Space);
             }
             self.markers.push(Marker::Token(comment));
         }
         if let Blank::Required = blanks {
-            self.markers.push(Marker::Blank);
+            self.markers.push(Marker::Blank(1));
         }
         let mut line = self.prev_line;
         for comment in comments {
-            if let Blank::Required = blanks {
-                if line > self.prev_line {
-                    if self.cst.tokens().start(comment).line - line > 1 {
-                        self.markers.push(Marker::Blank);
-                    } else {
-                        self.markers.push(Marker::Break);
-                    }
-                }
-            } else if self.cst.tokens().start(comment).line - line > 1 {
-                self.markers.push(Marker::Blank);
-            } else {
-                self.markers.push(Marker::Break);
+            if !matches!(blanks, Blank::Required) || line > self.prev_line {
+                let marker = self.line_break(line, self.cst.tokens().start(comment).line);
+                self.markers.push(marker);
             }
             self.markers.push(Marker::Token(comment));
             line = self.cst.tokens().end(comment).line;
         }
-        if let Blank::Legal = blanks {
-            if self.cst.tokens().start(tok).line - line > 1 {
-                self.markers.push(Marker::Blank);
-            } else {
-                self.markers.push(Marker::Break);
-            }
-        } else if let Blank::Illegal = blanks {
+        if let Blank::Illegal = blanks {
             self.markers.push(Marker::Break);
-        } else if line > self.prev_line {
-            if self.cst.tokens().start(tok).line - line > 1 {
-                self.markers.push(Marker::Blank);
-            } else {
+        } else if matches!(blanks, Blank::Legal) || line > self.prev_line {
+            let marker = self.line_break(line, self.cst.tokens().start(tok).line);
+            self.markers.push(marker);
+        }
+    }
+
+    /// Return comments from before the specified token that were not
+    /// handled yet. First vector contains inline comments.
+    fn comments_before(&mut self, tok: TokenID) -> (Vec<TokenID>, Vec<TokenID>) {
+        let tokens = self.cst.tokens();
+        let mut trivia = self.prev_tok.map_or(Vec::new(), |t| tokens.trailing_trivia(t));
+        trivia.extend(tokens.leading_trivia(tok));
+        let (inlines, comments) = trivia
+            .into_iter()
+            .filter_map(|t| match t {
+                Trivia::Token(c) if Some(c) > self.handled => Some(c),
+                _ => None,
+            })
+            .partition(|c| tokens.start(*c).line == self.prev_line);
+        self.handled = Some(tok);
+        (inlines, comments)
+    }
+
+    /// Return line break marker separating code that ends and starts
+    /// at the given lines. Blank lines are preserved up to the limit.
+    fn line_break(&self, end: usize, start: usize) -> Marker {
+        match (start - end).saturating_sub(1).min(self.options.max_blank_lines) {
+            0 => Marker::Break,
+            blanks => Marker::Blank(blanks),
+        }
+    }
+
+    /// Return marker separating code that ends and starts at the given lines
+    fn separator(&self, end: usize, start: usize) -> Marker {
+        if start == end {
+            Marker::Space
+        } else {
+            self.line_break(end, start)
+        }
+    }
+
+    /// Return blank line policy for the beginning and the end of the
+    /// class composition
+    fn composition_blanks(&self) -> Blank {
+        if self.options.blank_lines_in_composition {
+            Blank::Required
+        } else {
+            Blank::Legal
+        }
+    }
+
+    /// Return blank line policy for the beginning of sections and
+    /// their first elements
+    fn section_blanks(&self) -> Blank {
+        if self.options.blank_lines_around_sections {
+            Blank::Required
+        } else {
+            Blank::Legal
+        }
+    }
+
+    /// Insert comments that precede the first token
+    fn leading_comments(&mut self, tok: TokenID) {
+        let (inlines, comments) = self.comments_before(tok);
+        let tokens = self.cst.tokens();
+        let mut line = None;
+        for comment in inlines.into_iter().chain(comments) {
+            if let Some(line) = line {
+                self.markers.push(self.separator(line, tokens.start(comment).line));
+            }
+            self.markers.push(Marker::Token(comment));
+            line = Some(tokens.end(comment).line);
+        }
+        if let Some(line) = line {
+            // Blank lines are illegal before the within clause
+            if tokens.kind(tok) == TokenKind::Within {
                 self.markers.push(Marker::Break);
+            } else {
+                self.markers.push(self.separator(line, tokens.start(tok).line));
             }
         }
     }
 
-    /// Return comments from before the specified token.
-    /// First vector contains inline comments.
-    fn comments_before(&mut self, tok: TokenID) -> (Vec<TokenID>, Vec<TokenID>) {
-        let mut comments = Vec::new();
-        let mut inlines = Vec::new();
-        while let Some(comment) = self.comments.peek() {
-            if *comment < tok {
-                if self.cst.tokens().start(*comment).line == self.prev_line {
-                    inlines.push(self.comments.next().unwrap());
-                } else {
-                    comments.push(self.comments.next().unwrap());
+    /// Insert comments that follow the last token
+    fn flush_comments(&mut self) {
+        let tokens = self.cst.tokens();
+        let comments = match self.prev_tok {
+            Some(prev) => tokens.trailing_trivia(prev),
+            None => tokens.all().into_iter().map(Trivia::Token).collect(),
+        };
+        let mut line = self.prev_line;
+        for trivia in comments {
+            if let Trivia::Token(comment) = trivia {
+                if Some(comment) <= self.handled {
+                    continue;
                 }
-            } else {
-                break;
+                // Nothing to separate the first comment from
+                if !self.markers.is_empty() {
+                    self.markers.push(self.separator(line, tokens.start(comment).line));
+                }
+                self.markers.push(Marker::Token(comment));
+                line = tokens.end(comment).line;
             }
         }
-        (inlines, comments)
     }
 
     /// Collect token marker and update the last token data
     fn handle_token(&mut self, tok: TokenID) {
+        let tokens = self.cst.tokens();
+        // Numbers are normalized here, so the printed text is known when
+        // matrix columns are aligned
+        if self.options.normalize_numbers && tokens.kind(tok) == TokenKind::UReal {
+            let text = printing::normalize_number(tokens.text(tok));
+            self.replace_token(tok, Marker::Text(text));
+        } else {
+            self.replace_token(tok, Marker::Token(tok));
+        }
+    }
+
+    /// Collect marker that replaces the token and update the last token data
+    fn replace_token(&mut self, tok: TokenID, marker: Marker) {
         // Discard comments, as they are only allowed when line is wrapped
         let _ = self.comments_before(tok);
+        self.prev_tok = Some(tok);
         self.prev_line = self.cst.tokens().end(tok).line;
         self.prev_kind = self.cst.tokens().kind(tok);
-        self.prev_tok = tok;
-        self.markers.push(Marker::Token(tok));
+        self.markers.push(marker);
     }
 }
 
@@ -263,8 +356,6 @@ fn stored_definition(f: &mut Formatter, tree: TreeID) {
                 let kind = f.cst.tokens().kind(*tok);
                 if kind == TokenKind::Final {
                     f.handle_break(*tok, Blank::Legal);
-                } else if kind == TokenKind::Within && tok > &f.cst.tokens().first() {
-                    f.handle_break(*tok, Blank::Illegal);
                 }
                 f.handle_token(*tok);
                 if kind == TokenKind::Final || kind == TokenKind::Within {
@@ -335,7 +426,7 @@ fn long_class_specifier(f: &mut Formatter, tree: TreeID) {
             Child::Token(tok) => {
                 let kind = f.cst.tokens().kind(*tok);
                 if kind == TokenKind::End {
-                    f.handle_break(*tok, Blank::Required);
+                    f.handle_break(*tok, f.composition_blanks());
                 }
                 f.handle_token(*tok);
                 if kind == TokenKind::End || kind == TokenKind::Extends {
@@ -516,24 +607,31 @@ fn composition(f: &mut Formatter, tree: TreeID) {
                 match f.cst.kind(*tree) {
                     SyntaxKind::ElementList => {
                         f.markers.push(Marker::Indent);
-                        f.handle_break(f.cst.start(*tree), Blank::Required);
+                        f.handle_break(f.cst.start(*tree), f.composition_blanks());
                         element_list(f, *tree);
                         f.markers.push(Marker::Dedent);
                     }
                     SyntaxKind::EquationSection => {
-                        f.handle_break(f.cst.start(*tree), Blank::Required);
+                        f.handle_break(f.cst.start(*tree), f.section_blanks());
                         equation_section(f, *tree);
                     }
                     SyntaxKind::AlgorithmSection => {
-                        f.handle_break(f.cst.start(*tree), Blank::Required);
+                        f.handle_break(f.cst.start(*tree), f.section_blanks());
                         algorithm_section(f, *tree);
                     }
+                    #[cfg(feature = "optimica")]
+                    SyntaxKind::ConstraintSection => {
+                        f.handle_break(f.cst.start(*tree), f.section_blanks());
+                        constraint_section(f, *tree);
+                    }
                     SyntaxKind::LanguageSpecification => {
                         f.markers.push(Marker::Space);
                         language_specification(f, *tree);
-                        f.markers.push(Marker::Space);
                     }
-                    SyntaxKind::ExternalFunctionCall => external_function_call(f, *tree),
+                    SyntaxKind::ExternalFunctionCall => {
+                        f.handle_break(f.cst.start(*tree), Blank::Required);
+                        external_function_call(f, *tree);
+                    }
                     SyntaxKind::AnnotationClause => {
                         f.markers.push(Marker::Indent);
                         let extern_element_annotation = f.prev_kind == TokenKind::External
@@ -564,12 +662,12 @@ fn composition(f: &mut Formatter, tree: TreeID) {
             Child::Token(tok) => {
                 let kind = f.cst.tokens().kind(*tok);
                 if [TokenKind::Protected, TokenKind::Public, TokenKind::External].contains(&kind) {
-                    if kind == TokenKind::External {
-                        f.markers.push(Marker::Indent);
-                    }
-                    f.handle_break(*tok, Blank::Required);
+                    f.handle_break(*tok, f.section_blanks());
                 }
                 f.handle_token(*tok);
+                if kind == TokenKind::External {
+                    f.markers.push(Marker::Indent);
+                }
             }
         }
     }
@@ -606,7 +704,7 @@ fn external_function_call(f: &mut Formatter, tree: TreeID) {
         match child {
             Child::Tree(tree) => match f.cst.kind(*tree) {
                 SyntaxKind::ComponentReference => component_reference(f, *tree),
-                SyntaxKind::ExpressionList => expression_list(f, *tree, is_multiline),
+                SyntaxKind::ExpressionList => _ = expression_list(f, *tree, is_multiline),
                 _ => unreachable!(),
             },
             Child::Token(tok) => {
@@ -633,7 +731,17 @@ fn element_list(f: &mut Formatter, tree: TreeID) {
         match child {
             Child::Tree(tree) => {
                 if f.prev_kind == TokenKind::Semicolon {
-                    f.handle_break(f.cst.start(*tree), Blank::Legal);
+                    let is_class = f.cst.children(*tree).iter().any(
+                        |c| matches!(c, Child::Tree(t) if f.cst.kind(*t) == SyntaxKind::ClassDefinition),
+                    );
+                    f.handle_break(
+                        f.cst.start(*tree),
+                        if is_class && f.options.blank_line_before_classes {
+                            Blank::Required
+                        } else {
+                            Blank::Legal
+                        },
+                    );
                 }
                 element(f, *tree);
             }
@@ -1051,6 +1159,9 @@ fn element_modification(f: &mut Formatter, tree: TreeID) {
         if let Child::Tree(tree) = child {
             match f.cst.kind(*tree) {
                 SyntaxKind::Name => name(f, *tree),
+                SyntaxKind::Modification if f.options.html && is_html(f.cst, *tree) => {
+                    html_modification(f, *tree)
+                }
                 SyntaxKind::Modification => modification(f, *tree),
                 SyntaxKind::DescriptionString => {
                     f.markers.push(Marker::Indent);
@@ -1064,6 +1175,45 @@ fn element_modification(f: &mut Formatter, tree: TreeID) {
     }
 }
 
+/// Return `true` if the modification sets the `info` or `revisions`
+/// string of the `Documentation` annotation
+fn is_html(cst: &ModelicaCST, tree: TreeID) -> bool {
+    let name = |id: TreeID| {
+        cst.children(id).iter().find_map(|c| match c {
+            Child::Tree(t) if cst.kind(*t) == SyntaxKind::Name => Some(cst.text(*t)),
+            _ => None,
+        })
+    };
+    let tokens: Vec<TokenKind> = cst.tokens_in(tree).map(|t| cst.tokens().kind(t)).collect();
+    if tokens != [TokenKind::Equal, TokenKind::String] {
+        return false;
+    }
+    let mut modifications = cst
+        .ancestors(tree)
+        .filter(|a| cst.kind(*a) == SyntaxKind::ElementModification);
+    matches!(modifications.next().and_then(name), Some("info" | "revisions"))
+        && modifications.next().and_then(name) == Some("Documentation")
+        && cst
+            .ancestors(tree)
+            .any(|a| cst.kind(a) == SyntaxKind::AnnotationClause)
+}
+
+/// Format the modification that sets the HTML documentation string
+fn html_modification(f: &mut Formatter, tree: TreeID) {
+    for child in f.cst.children(tree) {
+        f.markers.push(Marker::Space);
+        match child {
+            Child::Tree(expr) => {
+                let tok = f.cst.start(*expr);
+                let text = f.cst.tokens().text(tok);
+                let marker = html::format(text).map_or(Marker::Token(tok), Marker::Text);
+                f.replace_token(tok, marker);
+            }
+            Child::Token(tok) => f.handle_token(*tok),
+        }
+    }
+}
+
 fn element_redeclaration(f: &mut Formatter, tree: TreeID) {
     for child in f.cst.children(tree) {
         match child {
@@ -1165,7 +1315,7 @@ fn equation_section(f: &mut Formatter, tree: TreeID) {
                 f.handle_break(
                     f.cst.start(*tree),
                     if f.prev_kind == TokenKind::Equation {
-                        Blank::Required
+                        f.section_blanks()
                     } else {
                         Blank::Legal
                     },
@@ -1192,7 +1342,7 @@ fn algorithm_section(f: &mut Formatter, tree: TreeID) {
                 f.handle_break(
                     f.cst.start(*tree),
                     if f.prev_kind == TokenKind::Algorithm {
-                        Blank::Required
+                        f.section_blanks()
                     } else {
                         Blank::Legal
                     },
@@ -1600,6 +1750,8 @@ fn expression(f: &mut Formatter, tree: TreeID, mut wrapped: bool, in_oel: bool)
                     conditional = false;
                 }
                 SyntaxKind::SimpleExpression => wrapped = simple_expression(f, *tree, wrapped),
+                #[cfg(feature = "metamodelica")]
+                SyntaxKind::MatchExpression => match_expression(f, *tree),
                 _ => unreachable!(),
             },
             Child::Token(tok) => {
@@ -1829,6 +1981,7 @@ fn factor(f: &mut Formatter, tree: TreeID, mut wrapped: bool) -> bool {
 fn primary(f: &mut Formatter, tree: TreeID, mut wrapped: bool) -> bool {
     let is_multiline = f.cst.is_multiline(tree);
     let children_count = f.cst.children(tree).len();
+    let mut rows = Vec::new();
     let mut children = f.cst.children(tree).iter().peekable();
     while let Some(child) = children.next() {
         match child {
@@ -1869,9 +2022,11 @@ fn primary(f: &mut Formatter, tree: TreeID, mut wrapped: bool) -> bool {
                 SyntaxKind::FunctionCallArgs => function_call_args(f, *tree),
                 SyntaxKind::ArraySubscripts => array_subscripts(f, *tree),
                 SyntaxKind::ArrayArguments => array_arguments(f, *tree, is_multiline),
-                SyntaxKind::ExpressionList => {
-                    expression_list(f, *tree, is_multiline && children_count == 3)
-                }
+                SyntaxKind::ExpressionList => rows.push(expression_list(
+                    f,
+                    *tree,
+                    is_multiline && children_count == 3,
+                )),
                 SyntaxKind::OutputExpressionList => {
                     wrapped = output_expression_list(f, *tree, wrapped)
                 }
@@ -1879,14 +2034,92 @@ fn primary(f: &mut Formatter, tree: TreeID, mut wrapped: bool) -> bool {
             },
         }
     }
+    if f.options.align_matrices && is_multiline && rows.len() > 1 {
+        align_columns(f, &rows);
+    }
     wrapped
 }
 
+/// Pad elements of matrix rows, so the columns are aligned. Numeric
+/// literals are aligned to the right, other elements to the left.
+/// Matrices with wrapped rows or elements are left untouched. Widths
+/// are counted in characters, not in display columns.
+fn align_columns(f: &mut Formatter, rows: &[Vec<(TreeID, usize, usize)>]) {
+    let tokens = f.cst.tokens();
+    let mut widths: Vec<Vec<usize>> = Vec::new();
+    for row in rows {
+        let mut row_widths = Vec::new();
+        for (_, start, end) in row {
+            let mut width = 0;
+            for marker in &f.markers[*start..*end] {
+                width += match marker {
+                    Marker::Token(tok) => tokens.text(*tok).chars().count(),
+                    Marker::Text(text) => text.chars().count(),
+                    Marker::Space => 1,
+                    Marker::Indent | Marker::Dedent => 0,
+                    Marker::Blank(_) | Marker::Break => return,
+                };
+            }
+            row_widths.push(width);
+        }
+        widths.push(row_widths);
+    }
+    let mut columns: Vec<usize> = Vec::new();
+    for row_widths in &widths {
+        for (i, width) in row_widths.iter().enumerate() {
+            match columns.get_mut(i) {
+                Some(column) => *column = (*column).max(*width),
+                None => columns.push(*width),
+            }
+        }
+    }
+    // Padding is inserted before elements, starting from the last one
+    // to keep marker positions valid
+    let mut paddings = Vec::new();
+    for (row, row_widths) in rows.iter().zip(&widths) {
+        let mut trailing = 0;
+        for (i, ((element, start, _), width)) in row.iter().zip(row_widths).enumerate() {
+            let pad = columns[i] - width;
+            if is_numeric(f.cst, *element) {
+                paddings.push((*start, trailing + pad));
+                trailing = 0;
+            } else {
+                paddings.push((*start, trailing));
+                trailing = pad;
+            }
+        }
+    }
+    for (position, pad) in paddings.into_iter().rev() {
+        f.markers.splice(position..position, (0..pad).map(|_| Marker::Space));
+    }
+}
+
+/// Return `true` if the expression is a numeric literal, optionally
+/// preceded by a sign
+fn is_numeric(cst: &ModelicaCST, tree: TreeID) -> bool {
+    let kinds: Vec<TokenKind> = cst.tokens_in(tree).map(|t| cst.tokens().kind(t)).collect();
+    matches!(
+        kinds.as_slice(),
+        [TokenKind::UInt | TokenKind::UReal]
+            | [TokenKind::Plus | TokenKind::Minus, TokenKind::UInt | TokenKind::UReal]
+    )
+}
+
 fn type_specifier(f: &mut Formatter, tree: TreeID) {
     for child in f.cst.children(tree) {
         match child {
-            Child::Tree(t) => name(f, *t),
-            Child::Token(tok) => f.handle_token(*tok),
+            Child::Tree(t) => match f.cst.kind(*t) {
+                SyntaxKind::Name => name(f, *t),
+                // MetaModelica type parameters
+                SyntaxKind::TypeSpecifier => type_specifier(f, *t),
+                _ => unreachable!(),
+            },
+            Child::Token(tok) => {
+                f.handle_token(*tok);
+                if f.prev_kind == TokenKind::Comma {
+                    f.markers.push(Marker::Space);
+                }
+            }
         }
     }
 }
@@ -2136,16 +2369,26 @@ fn output_expression_list(f: &mut Formatter, tree: TreeID, mut wrapped: bool) ->
     wrapped
 }
 
-fn expression_list(f: &mut Formatter, tree: TreeID, mut is_multiline: bool) {
+/// Return expressions from the list with ranges of their markers
+fn expression_list(
+    f: &mut Formatter,
+    tree: TreeID,
+    mut is_multiline: bool,
+) -> Vec<(TreeID, usize, usize)> {
     // Expression list could be already wrapped in an outer production
     // at the brackets or parentheses
     if !is_multiline {
         is_multiline = f.cst.is_multiline(tree);
     }
+    let mut elements = Vec::new();
     let mut children = f.cst.children(tree).iter().peekable();
     while let Some(child) = children.next() {
         match child {
-            Child::Tree(t) => _ = expression(f, *t, false, false),
+            Child::Tree(t) => {
+                let start = f.markers.len();
+                _ = expression(f, *t, false, false);
+                elements.push((*t, start, f.markers.len()));
+            }
             Child::Token(tok) => {
                 f.handle_token(*tok);
                 if let Child::Tree(next_tree) = children.peek().unwrap() {
@@ -2154,6 +2397,7 @@ fn expression_list(f: &mut Formatter, tree: TreeID, mut is_multiline: bool) {
             }
         }
     }
+    elements
 }
 
 fn array_subscripts(f: &mut Formatter, tree: TreeID) {
@@ -2240,3 +2484,155 @@ fn annotation_clause(f: &mut Formatter, tree: TreeID) {
         }
     }
 }
+
+#[cfg(feature = "optimica")]
+fn constraint_section(f: &mut Formatter, tree: TreeID) {
+    f.markers.push(Marker::Indent);
+    for child in f.cst.children(tree) {
+        match child {
+            Child::Tree(tree) => {
+                f.handle_break(
+                    f.cst.start(*tree),
+                    if f.prev_kind == TokenKind::Constraint {
+                        f.section_blanks()
+                    } else {
+                        Blank::Legal
+                    },
+                );
+                equation(f, *tree);
+            }
+            Child::Token(tok) => f.handle_token(*tok),
+        }
+    }
+    f.markers.push(Marker::Dedent);
+}
+
+#[cfg(feature = "metamodelica")]
+fn match_expression(f: &mut Formatter, tree: TreeID) {
+    for child in f.cst.children(tree) {
+        match child {
+            Child::Tree(tree) => match f.cst.kind(*tree) {
+                SyntaxKind::Expression => {
+                    f.markers.push(Marker::Space);
+                    expression(f, *tree, false, false);
+                }
+                SyntaxKind::DescriptionString => {
+                    f.markers.push(Marker::Space);
+                    description_string(f, *tree);
+                }
+                SyntaxKind::ElementList => {
+                    f.markers.push(Marker::Indent);
+                    f.markers.push(Marker::Indent);
+                    f.handle_break(f.cst.start(*tree), Blank::Illegal);
+                    element_list(f, *tree);
+                    f.markers.push(Marker::Dedent);
+                    f.markers.push(Marker::Dedent);
+                }
+                SyntaxKind::MatchCase => {
+                    f.markers.push(Marker::Indent);
+                    f.handle_break(f.cst.start(*tree), Blank::Legal);
+                    match_case(f, *tree);
+                    f.markers.push(Marker::Dedent);
+                }
+                _ => unreachable!(),
+            },
+            Child::Token(tok) => {
+                let kind = f.cst.tokens().kind(*tok);
+                match kind {
+                    TokenKind::Local => {
+                        f.markers.push(Marker::Indent);
+                        f.handle_break(*tok, Blank::Illegal);
+                        f.handle_token(*tok);
+                        f.markers.push(Marker::Dedent);
+                    }
+                    TokenKind::End => {
+                        f.handle_break(*tok, Blank::Illegal);
+                        f.handle_token(*tok);
+                        f.markers.push(Marker::Space);
+                    }
+                    _ => f.handle_token(*tok),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "metamodelica")]
+fn match_case(f: &mut Formatter, tree: TreeID) {
+    let mut has_body = false;
+    for child in f.cst.children(tree) {
+        match child {
+            Child::Tree(tree) => match f.cst.kind(*tree) {
+                SyntaxKind::Expression => {
+                    f.markers.push(Marker::Space);
+                    expression(f, *tree, false, false);
+                }
+                SyntaxKind::Equation => {
+                    f.handle_break(f.cst.start(*tree), Blank::Legal);
+                    equation(f, *tree);
+                }
+                SyntaxKind::Statement => {
+                    f.handle_break(f.cst.start(*tree), Blank::Legal);
+                    statement(f, *tree);
+                }
+                _ => unreachable!(),
+            },
+            Child::Token(tok) => {
+                let kind = f.cst.tokens().kind(*tok);
+                match kind {
+                    TokenKind::Guard => {
+                        f.markers.push(Marker::Space);
+                        f.handle_token(*tok);
+                    }
+                    TokenKind::Equation | TokenKind::Algorithm => {
+                        has_body = true;
+                        f.markers.push(Marker::Indent);
+                        f.handle_break(*tok, Blank::Illegal);
+                        f.handle_token(*tok);
+                        f.markers.push(Marker::Indent);
+                    }
+                    TokenKind::Then if has_body => {
+                        f.markers.push(Marker::Dedent);
+                        f.handle_break(*tok, Blank::Illegal);
+                        f.handle_token(*tok);
+                        f.markers.push(Marker::Dedent);
+                    }
+                    TokenKind::Then => {
+                        f.markers.push(Marker::Space);
+                        f.handle_token(*tok);
+                    }
+                    _ => f.handle_token(*tok),
+                }
+            }
+        }
+    }
+}
+
+fn script(f: &mut Formatter, tree: TreeID) {
+    for child in f.cst.children(tree) {
+        match child {
+            Child::Tree(tree) => {
+                if f.prev_kind == TokenKind::Semicolon {
+                    f.handle_break(f.cst.start(*tree), Blank::Legal);
+                }
+                match f.cst.kind(*tree) {
+                    SyntaxKind::ImportClause => import_clause(f, *tree),
+                    SyntaxKind::ClassDefinition => class_definition(f, *tree),
+                    SyntaxKind::Statement => statement(f, *tree),
+                    SyntaxKind::Expression => _ = expression(f, *tree, false, false),
+                    _ => unreachable!(),
+                }
+            }
+            Child::Token(tok) => {
+                let kind = f.cst.tokens().kind(*tok);
+                if kind == TokenKind::Final && f.prev_kind == TokenKind::Semicolon {
+                    f.handle_break(*tok, Blank::Legal);
+                }
+                f.handle_token(*tok);
+                if kind == TokenKind::Final {
+                    f.markers.push(Marker::Space);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Pretty-printing of HTML stored in string literals of the
+//! `Documentation` annotation.
+//!
+//! HTML is processed without unescaping the Modelica string, so escape
+//! sequences like `\"` are kept as they are. Block elements are placed on
+//! separate lines and their content is indented. Whitespace inside the
+//! inline content is collapsed, and contents of `pre`, `script`, `style`
+//! and `textarea` elements are kept verbatim.
+
+/// Elements that are placed on separate lines
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "meta",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "table",
+    "tbody",
+    "td",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Elements that have no content and closing tags
+const VOIDS: &[&str] = &[
+    "area", "base", "br", "col", "hr", "img", "input", "link", "meta", "param", "source", "wbr",
+];
+
+/// Elements which content is kept verbatim
+const VERBATIM: &[&str] = &["pre", "script", "style", "textarea"];
+
+const INDENT: &str = "  ";
+
+#[derive(Debug, PartialEq)]
+/// Represents a fragment of the HTML document
+enum Node<'a> {
+    Tag {
+        text: &'a str,
+        name: String,
+        closing: bool,
+    },
+    Text(&'a str),
+    Comment(&'a str),
+    /// Content of the element that must not be modified
+    Verbatim(&'a str),
+}
+
+impl Node<'_> {
+    /// Return `true` if node is a tag of the block element
+    fn is_block(&self) -> bool {
+        matches!(self, Node::Tag { name, .. } if BLOCKS.contains(&name.as_str()))
+    }
+}
+
+/// Return the string literal with reformatted HTML content.
+///
+/// Return `None` if literal doesn't contain HTML, if HTML can't be
+/// parsed, or if the formatted content is not equivalent to the
+/// original one.
+pub fn format(literal: &str) -> Option<String> {
+    let content = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let nodes = parse(content)?;
+    if !nodes.iter().any(|n| n.is_block()) {
+        return None;
+    }
+    let formatted = print(&nodes);
+    if normalize(&unescape(content))? != normalize(&unescape(&formatted))? {
+        return None;
+    }
+    Some(format!("\"{}\"", formatted))
+}
+
+/// Split the HTML into nodes. Return `None` if tags or comments are
+/// not closed.
+fn parse(html: &str) -> Option<Vec<Node<'_>>> {
+    let mut nodes = Vec::new();
+    let mut rest = html;
+    let mut text_len = 0;
+    while text_len < rest.len() {
+        let tail = &rest[text_len..];
+        let Some(i) = tail.find('<') else {
+            text_len = rest.len();
+            break;
+        };
+        let tag = &tail[i..];
+        let is_markup = tag[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if !is_markup {
+            text_len += i + 1;
+            continue;
+        }
+        if text_len + i > 0 {
+            nodes.push(Node::Text(&rest[..text_len + i]));
+        }
+        let len = if tag.starts_with("<!--") {
+            let len = tag.find("-->")? + 3;
+            nodes.push(Node::Comment(&tag[..len]));
+            len
+        } else {
+            let len = tag.find('>')? + 1;
+            let closing = tag.starts_with("</");
+            let name: String = tag[if closing { 2 } else { 1 }..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            let verbatim = !closing && VERBATIM.contains(&name.as_str());
+            nodes.push(Node::Tag {
+                text: &tag[..len],
+                name: name.clone(),
+                closing,
+            });
+            if verbatim {
+                // Content ends with the matching closing tag
+                let content = &tag[len..];
+                let end = content.to_ascii_lowercase().find(&format!("</{}", name))?;
+                if end > 0 {
+                    nodes.push(Node::Verbatim(&content[..end]));
+                }
+                len + end
+            } else {
+                len
+            }
+        };
+        rest = &tag[len..];
+        text_len = 0;
+    }
+    if text_len > 0 {
+        nodes.push(Node::Text(rest));
+    }
+    Some(nodes)
+}
+
+/// Return HTML with block elements placed on separate lines
+fn print(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    let mut line = String::new();
+    let mut depth: usize = 0;
+    let mut i = 0;
+    while i < nodes.len() {
+        let node = &nodes[i];
+        i += 1;
+        match node {
+            Node::Tag {
+                text,
+                name,
+                closing,
+            } if node.is_block() => {
+                push_line(&mut out, &line, depth);
+                line.clear();
+                // Root element doesn't indent its content
+                let indents = name != "html" && !VOIDS.contains(&name.as_str());
+                if *closing && indents {
+                    depth = depth.saturating_sub(1);
+                }
+                let mut tag = String::from(*text);
+                if *closing || !indents {
+                    push_line(&mut out, &tag, depth);
+                    continue;
+                }
+                // Elements with inline content only are kept in one line
+                let end = nodes[i..]
+                    .iter()
+                    .position(|n| n.is_block())
+                    .map_or(nodes.len(), |n| n + i);
+                let inline = VERBATIM.contains(&name.as_str())
+                    || matches!(&nodes.get(end), Some(Node::Tag { name: n, closing: true, .. }) if n == name);
+                if inline {
+                    let content: String = nodes[i..end].iter().map(inline_text).collect();
+                    if VERBATIM.contains(&name.as_str()) {
+                        tag.push_str(&content);
+                    } else {
+                        tag.push_str(content.trim());
+                    }
+                    if let Some(Node::Tag { text, .. }) = nodes.get(end) {
+                        tag.push_str(text);
+                    }
+                    push_line(&mut out, &tag, depth);
+                    i = end + 1;
+                } else {
+                    push_line(&mut out, &tag, depth);
+                    depth += 1;
+                }
+            }
+            _ => line.push_str(&inline_text(node)),
+        }
+    }
+    push_line(&mut out, &line, depth);
+    out.pop();
+    out
+}
+
+/// Return text of the node that is a part of the inline content
+fn inline_text(node: &Node) -> String {
+    match node {
+        Node::Text(text) => collapse(text),
+        Node::Tag { text, .. } | Node::Comment(text) | Node::Verbatim(text) => String::from(*text),
+    }
+}
+
+/// Append the indented line to the output if it isn't blank
+fn push_line(out: &mut String, line: &str, depth: usize) {
+    let line = line.trim();
+    if !line.is_empty() {
+        (0..depth).for_each(|_| out.push_str(INDENT));
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Replace whitespace sequences with single spaces
+fn collapse(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+/// Return the value of the string literal content
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0C'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\x0B'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Return the sequence of HTML fragments that doesn't depend on the
+/// whitespace that is insignificant for rendering
+fn normalize(html: &str) -> Option<Vec<String>> {
+    let nodes = parse(html)?;
+    let mut normalized = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        match node {
+            Node::Text(text) => {
+                let mut text = collapse(text);
+                if i == 0 || nodes[i - 1].is_block() {
+                    text = String::from(text.trim_start());
+                }
+                if nodes.get(i + 1).is_none_or(|n| n.is_block()) {
+                    text = String::from(text.trim_end());
+                }
+                if !text.is_empty() {
+                    normalized.push(text);
+                }
+            }
+            Node::Tag { text, .. } | Node::Comment(text) | Node::Verbatim(text) => {
+                normalized.push(String::from(*text))
+            }
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_formatting() {
+        let literal = "\"<html><head><title>Foo</title></head><body>\n<h4>Foo   model</h4><p>Model with <b>bold</b>\n  text and <a href=\\\"modelica://Foo\\\">link</a>.</p><ul><li>one</li><li>two<br>lines</li></ul>\n<pre>  x := 1;\n  y := 2;</pre></body></html>\"";
+        let expected = "\"<html>\n<head>\n  <title>Foo</title>\n</head>\n<body>\n  <h4>Foo model</h4>\n  <p>Model with <b>bold</b> text and <a href=\\\"modelica://Foo\\\">link</a>.</p>\n  <ul>\n    <li>one</li>\n    <li>two<br>lines</li>\n  </ul>\n  <pre>  x := 1;\n  y := 2;</pre>\n</body>\n</html>\"";
+        assert_eq!(format(literal).as_deref(), Some(expected));
+        // Formatting is stable
+        assert_eq!(format(expected).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn test_html_edge_cases() {
+        // Not an HTML
+        assert_eq!(format("\"x < y\""), None);
+        assert_eq!(format("\"<b>bold</b>\""), None);
+        // Unclosed tags and comments
+        assert_eq!(format("\"<html><p>foo</p\""), None);
+        assert_eq!(format("\"<html><!-- foo</html>\""), None);
+        // Comments, void elements and comparisons in text
+        assert_eq!(
+            format("\"<html><!-- c --><p>x < y</p><hr/></html>\"").as_deref(),
+            Some("\"<html>\n<!-- c -->\n<p>x < y</p>\n<hr/>\n</html>\"")
+        );
+    }
+
+    #[test]
+    fn test_html_normalization() {
+        assert_eq!(
+            normalize("<p>\n  a  <b>b</b>\n</p>"),
+            normalize("<p>a <b>b</b></p>")
+        );
+        assert_ne!(normalize("<p>a<b>b</b></p>"), normalize("<p>a <b>b</b></p>"));
+        assert_eq!(unescape("\\\"a\\\\\\n"), "\"a\\\n");
+    }
+}
@@ -1,28 +1,92 @@
-use crate::parser::ModelicaCST;
+use crate::parser::{ModelicaCST, TokenKind};
 
 use super::formatting::Marker;
+use super::FormatOptions;
 
-pub fn print(cst: &ModelicaCST, markers: Vec<Marker>) -> String {
-    let mut printer = Printer::new();
-    let formatted: Vec<String> = markers
-        .into_iter()
-        .filter_map(|m| printer.print_marker(cst, m))
-        .collect();
-    formatted.join("")
+const INDENT: &str = "  ";
+const EOL: &str = if cfg!(windows) { "\r\n" } else { "\n" };
+
+const BOM: char = '\u{feff}';
+
+/// Return the formatted code. Lines don't end with whitespace, except
+/// lines that end inside string literals, and the code ends with a
+/// single line break. Code without any tokens or comments is empty.
+pub fn print(cst: &ModelicaCST, markers: Vec<Marker>, options: &FormatOptions) -> String {
+    let mut printer = Printer::new(options);
+    let mut formatted = String::new();
+    if !options.remove_bom && cst.tokens().code().starts_with(BOM) {
+        formatted.push(BOM);
+    }
+    for m in markers {
+        let is_string = match m {
+            Marker::Token(tok) => cst.tokens().kind(tok) == TokenKind::String,
+            Marker::Text(_) => true,
+            _ => false,
+        };
+        let Some(out) = printer.print_marker(cst, m) else {
+            continue;
+        };
+        printer.advance(&out);
+        if is_string {
+            formatted.push_str(&out);
+            continue;
+        }
+        let mut lines = out.split('\n');
+        formatted.push_str(lines.next().unwrap_or_default());
+        for line in lines {
+            // Strings end with quotes, so only the code is trimmed
+            let cr = formatted.ends_with('\r');
+            if cr {
+                formatted.pop();
+            }
+            formatted.truncate(formatted.trim_end_matches([' ', '\t']).len());
+            formatted.push_str(if cr { "\r\n" } else { "\n" });
+            formatted.push_str(line);
+        }
+    }
+    formatted.truncate(formatted.trim_end().len());
+    // Byte order mark alone is not worth preserving
+    if formatted.trim_start_matches(BOM).is_empty() {
+        return String::new();
+    }
+    formatted.push_str(EOL);
+    formatted
 }
 
-struct Printer {
+struct Printer<'a> {
     indent: usize,
+    /// Number of characters in the current line
+    column: usize,
+    /// `true` if the current line contains only the indentation
+    line_start: bool,
+    options: &'a FormatOptions,
 }
 
-impl Printer {
-    fn new() -> Self {
-        Printer { indent: 0 }
+impl<'a> Printer<'a> {
+    fn new(options: &'a FormatOptions) -> Self {
+        Printer {
+            indent: 0,
+            column: 0,
+            line_start: true,
+            options,
+        }
+    }
+
+    /// Update the position in the current line after printing the text
+    fn advance(&mut self, text: &str) {
+        match text.rfind('\n') {
+            Some(i) => {
+                self.column = text[i + 1..].chars().count();
+                self.line_start = text[i + 1..].trim().is_empty();
+            }
+            None => {
+                self.column += text.chars().count();
+                self.line_start &= text.trim().is_empty();
+            }
+        }
     }
 
     fn print_marker(&mut self, cst: &ModelicaCST, m: Marker) -> Option<String> {
-        const INDENT: &str = "  ";
-        const EOL: &str = if cfg!(windows) { "\r\n" } else { "\n" };
         match m {
             Marker::Space => Some(String::from(" ")),
             Marker::Indent => {
@@ -33,15 +97,186 @@ impl Printer {
                 self.indent -= 1;
                 None
             }
-            Marker::Token(tok) => Some(String::from(cst.tokens().text(tok))),
+            Marker::Token(tok) => {
+                let text = cst.tokens().text(tok);
+                match cst.tokens().kind(tok) {
+                    TokenKind::LineComment => Some(self.line_comment(text)),
+                    TokenKind::BlockComment if self.options.normalize_comments => {
+                        Some(self.block_comment(text, cst.tokens().start(tok).col - 1))
+                    }
+                    _ => Some(String::from(text)),
+                }
+            }
+            Marker::Text(text) => Some(text),
             _ => {
                 let mut out = String::from(EOL);
-                if m == Marker::Blank {
-                    out += EOL;
+                if let Marker::Blank(blanks) = m {
+                    (0..blanks).for_each(|_| out += EOL);
                 }
                 (0..self.indent).for_each(|_| out += INDENT);
                 Some(out)
             }
         }
     }
+
+    /// Return the line comment, normalized and wrapped according to the
+    /// options
+    fn line_comment(&self, text: &str) -> String {
+        // Dividers made of slashes or other symbols are kept as they are
+        if !self.options.normalize_comments || is_divider(text) {
+            return String::from(text);
+        }
+        let comment = normalize_line_comment(text);
+        match self.options.comment_width {
+            // Only comments that start the line are wrapped
+            Some(width) if self.line_start && self.column + comment.chars().count() > width => {
+                let mut indent = String::from(EOL);
+                (0..self.column).for_each(|_| indent.push(' '));
+                wrap_line_comment(&comment, width.saturating_sub(self.column)).join(&indent)
+            }
+            _ => comment,
+        }
+    }
+
+    /// Return the block comment without trailing whitespace and with
+    /// continuation lines shifted by the same distance as its first line.
+    /// `col` is the 0-based column of the comment in the original code.
+    fn block_comment(&self, text: &str, col: usize) -> String {
+        let mut lines = text.lines().map(str::trim_end);
+        let mut out = String::from(lines.next().unwrap_or_default());
+        for line in lines {
+            out.push_str(EOL);
+            if line.is_empty() {
+                continue;
+            }
+            if self.column >= col {
+                (col..self.column).for_each(|_| out.push(' '));
+                out.push_str(line);
+            } else {
+                let indent = line.len() - line.trim_start().len();
+                let chars = line
+                    .char_indices()
+                    .take(col - self.column)
+                    .take_while(|(i, _)| *i < indent);
+                let cut = chars.last().map_or(0, |(i, c)| i + c.len_utf8());
+                out.push_str(&line[cut..]);
+            }
+        }
+        out
+    }
+}
+
+/// Return the line comment without trailing whitespace and with a space
+/// after the leading slashes
+fn normalize_line_comment(text: &str) -> String {
+    let body = text[2..].trim_end();
+    if body.is_empty() || body.starts_with([' ', '\t', '/']) {
+        format!("//{}", body)
+    } else {
+        format!("// {}", body)
+    }
+}
+
+/// Return `true` if the line comment contains no letters or digits
+fn is_divider(text: &str) -> bool {
+    !text[2..].chars().any(char::is_alphanumeric)
+}
+
+/// Split the line comment into comments that fit in the given width if
+/// possible. Lines are broken only at whitespace, which is otherwise
+/// preserved, and every line starts like the original comment. Words
+/// longer than the width are never broken.
+fn wrap_line_comment(text: &str, width: usize) -> Vec<String> {
+    let body = &text[2..];
+    let mut rest = body.trim_start();
+    let prefix = format!("//{}", &body[..body.len() - rest.len()]);
+    let mut lines = Vec::new();
+    let mut line = prefix.clone();
+    let mut space = "";
+    while !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        let len = line.chars().count() + space.chars().count() + word.chars().count();
+        if line.len() > prefix.len() && len > width {
+            lines.push(std::mem::replace(&mut line, prefix.clone()));
+        } else {
+            line.push_str(space);
+        }
+        line.push_str(word);
+        let next = rest[end..].trim_start();
+        space = &rest[end..rest.len() - next.len()];
+        rest = next;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Return the real literal with lowercase exponent marker, without the
+/// `+` sign in the exponent and with zero after the trailing decimal point
+pub(super) fn normalize_number(text: &str) -> String {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let mut out = String::from(mantissa);
+    if out.ends_with('.') {
+        out.push('0');
+    }
+    if let Some(exponent) = exponent {
+        out.push('e');
+        out.push_str(exponent.strip_prefix('+').unwrap_or(exponent));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_number() {
+        let cases = [
+            ("1.", "1.0"),
+            ("1.0", "1.0"),
+            ("1E3", "1e3"),
+            ("1e+3", "1e3"),
+            ("1.E+03", "1.0e03"),
+            ("2.5e-3", "2.5e-3"),
+            ("0.e0", "0.0e0"),
+            ("00.100E-0", "00.100e-0"),
+            ("123456789.123456789e+300", "123456789.123456789e300"),
+        ];
+        for (text, expected) in cases {
+            let normalized = normalize_number(text);
+            assert_eq!(normalized, expected);
+            assert_eq!(normalized.parse::<f64>(), text.parse::<f64>());
+            // Normalization is stable
+            assert_eq!(normalize_number(&normalized), normalized);
+        }
+    }
+
+    #[test]
+    fn test_line_comments() {
+        assert_eq!(normalize_line_comment("//foo  "), "// foo");
+        assert_eq!(normalize_line_comment("// foo"), "// foo");
+        assert_eq!(normalize_line_comment("//\tfoo"), "//\tfoo");
+        assert_eq!(normalize_line_comment("//////"), "//////");
+        assert_eq!(normalize_line_comment("//   "), "//");
+        assert_eq!(
+            wrap_line_comment("// foo bar  baz", 10),
+            vec!["// foo bar", "// baz"]
+        );
+        assert_eq!(
+            wrap_line_comment("// foo verylongword", 8),
+            vec!["// foo", "// verylongword"]
+        );
+        assert_eq!(wrap_line_comment("//", 8), vec!["//"]);
+        assert_eq!(
+            wrap_line_comment("//   a  = b  c", 12),
+            vec!["//   a  = b", "//   c"]
+        );
+        assert!(is_divider("//////////"));
+        assert!(is_divider("// ---- * ----"));
+        assert!(!is_divider("// -- a --"));
+    }
 }
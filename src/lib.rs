@@ -1,7 +1,15 @@
 
 mod parser;
 mod formatter;
+mod linter;
+mod fixer;
+mod dumper;
+mod packages;
 
 // Re-exports
 
 pub use parser::*;
+pub use linter::Diagnostic;
+pub use fixer::{Fix, TextEdit};
+pub use formatter::{format_str, FormatError, FormatOptions};
+pub use packages::{check_package_order, merge_package, PackageFile, PackageOrder};
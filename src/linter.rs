@@ -0,0 +1,182 @@
+mod imports;
+mod location;
+
+use crate::fixer::Fix;
+use crate::parser::{ModelicaCST, TokenID, Tokens};
+
+/// Represents a single problem reported by one of the lint rules.
+///
+/// # Fields
+/// - `rule`: name of the rule that reported the problem
+/// - `token`: token at which the problem is located
+/// - `msg`: human readable description of the problem
+/// - `fix`: optional automatic fix
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub token: TokenID,
+    pub msg: String,
+    pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    /// Return formatted diagnostic message
+    pub fn message(&self, tokens: &Tokens) -> String {
+        let pos = tokens.start(self.token);
+        format!(
+            "{}:{}:{}: {} [{}]",
+            tokens.source(),
+            pos.line,
+            pos.col,
+            self.msg,
+            self.rule
+        )
+    }
+}
+
+impl ModelicaCST {
+    /// Return diagnostics reported by all lint rules.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        imports::check(self, &mut diagnostics);
+        diagnostics.sort_by_key(|d| d.token);
+        diagnostics
+    }
+
+    /// Return diagnostics reported by rules that check if the code
+    /// matches the location of the file in the library.
+    ///
+    /// `package` is the full name of the package that contains the file
+    /// (empty for the top-level package), and `class` is the name of the
+    /// class implied by the file or directory name.
+    pub fn lint_location(&self, package: &str, class: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        location::check(self, package, class, &mut diagnostics);
+        diagnostics.sort_by_key(|d| d.token);
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixer::Fix;
+    use crate::formatter::FormatOptions;
+    use crate::parser::{EntryPoint, ModelicaCST};
+
+    fn lint(code: &str) -> Vec<String> {
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        );
+        cst.lint().iter().map(|d| d.message(cst.tokens())).collect()
+    }
+
+    #[test]
+    fn test_used_imports() {
+        let code = "model Foo\n import SI = Modelica.Units.SI;\n import Modelica.Constants.pi;\n import Modelica.Math.{sin, cos};\n SI.Length l = pi * sin(1.0) * cos(2.0);\nend Foo;";
+        assert!(lint(code).is_empty());
+    }
+
+    #[test]
+    fn test_unused_imports() {
+        let code = "model Foo\n import SI = Modelica.Units.SI;\n import Modelica.Constants.pi;\n import Modelica.Math.{sin, cos};\n Real x = sin(1.0);\nend Foo;";
+        assert_eq!(
+            lint(code),
+            vec![
+                "test:2:9: unused import 'SI' [unused-import]",
+                "test:3:28: unused import 'pi' [unused-import]",
+                "test:4:29: unused import 'cos' [unused-import]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_imports() {
+        let code = "package Foo\n import Modelica.Constants.pi;\n import Modelica.Constants.pi;\n model Bar\n  Real x = pi;\n end Bar;\nend Foo;";
+        assert_eq!(
+            lint(code),
+            vec!["test:3:28: duplicate import 'pi' [duplicate-import]"]
+        );
+    }
+
+    #[test]
+    fn test_import_fixes() {
+        let code = "model Foo\n import A.B;\n import C.{D, E};\n import F.G;\n Real x = G.y + E;\nend Foo;";
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        );
+        let fixes: Vec<(usize, usize)> = cst
+            .lint()
+            .iter()
+            .filter_map(|d| d.fix.as_ref())
+            .map(|f| {
+                (
+                    cst.tokens().start(f.edits[0].start).line,
+                    cst.tokens().end(f.edits[0].end).col,
+                )
+            })
+            .collect();
+        // Only the clauses where every imported name is unused are removed
+        assert_eq!(fixes, vec![(2, 13)]);
+    }
+
+    fn lint_location(code: &str, package: &str, class: &str) -> (Vec<String>, String) {
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        );
+        let diagnostics = cst.lint_location(package, class);
+        let fixes: Vec<&Fix> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+        let messages = diagnostics.iter().map(|d| d.message(cst.tokens())).collect();
+        (messages, cst.apply_fixes(&fixes, &FormatOptions::new()).0)
+    }
+
+    #[test]
+    fn test_location() {
+        let code = "within Lib.A;\nmodel B\nend B;";
+        assert!(lint_location(code, "Lib.A", "B").0.is_empty());
+        assert!(lint_location("package Lib end Lib;", "", "Lib").0.is_empty());
+        assert!(lint_location("within; package Lib end Lib;", "", "Lib").0.is_empty());
+        // Files with multiple classes are not checked
+        assert!(lint_location("model B end B; model C end C;", "Lib", "B").0.is_empty());
+    }
+
+    #[test]
+    fn test_location_mismatch() {
+        let (messages, fixed) = lint_location("within Lib.X;\nmodel C\nend C;", "Lib.A", "B");
+        assert_eq!(
+            messages,
+            vec![
+                "test:1:8: within clause 'Lib.X' doesn't match the file location, expected 'Lib.A' [within-mismatch]",
+                "test:2:7: class name 'C' doesn't match the file name, expected 'B' [class-name-mismatch]",
+            ]
+        );
+        assert_eq!(fixed, "within Lib.A;\nmodel B\n\nend B;\n");
+        let (messages, fixed) = lint_location("// foo\ntype T = Real;", "Lib", "T");
+        assert_eq!(
+            messages,
+            vec!["test:2:1: missing within clause 'within Lib;' [within-mismatch]"]
+        );
+        assert_eq!(fixed, "// foo\nwithin Lib;\ntype T = Real;\n");
+        let (_, fixed) = lint_location("within;\ntype T = Real;", "Lib", "T");
+        assert_eq!(fixed, "within Lib;\ntype T = Real;\n");
+        let (_, fixed) = lint_location("within Foo;\npackage Lib end Lib;", "", "Lib");
+        assert_eq!(fixed, "package Lib\n\nend Lib;\n");
+    }
+
+    #[test]
+    fn test_location_invalid_names() {
+        // Names that can't be used in the code are not checked
+        let code = "within Lib;\nmodel B\nend B;";
+        assert!(lint_location(code, "Lib 1.0", "B").0.is_empty());
+        assert!(lint_location(code, "Lib", "B 2").0.is_empty());
+        assert!(lint_location(code, "Lib", "model").0.is_empty());
+        assert!(lint_location(code, "Lib", "A.B").0.is_empty());
+        let (messages, fixed) = lint_location(code, "Lib", "'B 2'");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(fixed, "within Lib;\nmodel 'B 2'\n\nend 'B 2';\n");
+    }
+}
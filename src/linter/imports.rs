@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use crate::parser::*;
+
+use crate::fixer::{Fix, TextEdit};
+
+use super::Diagnostic;
+
+/// Single name introduced into the class scope by an import clause
+struct Import {
+    /// Name under which imported element is visible
+    short: String,
+    /// Fully qualified name of the imported element
+    path: String,
+    /// Token that introduces the short name
+    token: TokenID,
+}
+
+/// Report unused and duplicate import clauses in every class
+pub fn check(cst: &ModelicaCST, diagnostics: &mut Vec<Diagnostic>) {
+    if let Some(root) = cst.root() {
+        let classes: Vec<TreeID> = cst
+            .descendants(root)
+            .filter(|t| cst.kind(*t) == SyntaxKind::ClassDefinition)
+            .collect();
+        for class in classes {
+            check_class(cst, class, diagnostics);
+        }
+    }
+}
+
+fn check_class(cst: &ModelicaCST, class: TreeID, diagnostics: &mut Vec<Diagnostic>) {
+    let mut scope = Scope {
+        class,
+        clauses: Vec::new(),
+        used: HashSet::new(),
+    };
+    cst.walk(class, &mut scope);
+    let Scope { clauses, used, .. } = scope;
+    if clauses.is_empty() {
+        return;
+    }
+    let mut seen: Vec<Import> = Vec::new();
+    for clause in clauses {
+        let imports = imported_names(cst, clause);
+        let mut removable = !imports.is_empty();
+        for import in imports {
+            if let Some(prev) = seen.iter().find(|i| i.short == import.short) {
+                removable &= prev.path == import.path;
+                diagnostics.push(Diagnostic {
+                    rule: "duplicate-import",
+                    token: import.token,
+                    msg: format!("duplicate import '{}'", import.short),
+                    fix: None,
+                });
+            } else {
+                if !used.contains(&import.short) {
+                    diagnostics.push(Diagnostic {
+                        rule: "unused-import",
+                        token: import.token,
+                        msg: format!("unused import '{}'", import.short),
+                        fix: None,
+                    });
+                } else {
+                    removable = false;
+                }
+                seen.push(import);
+            }
+        }
+        if removable {
+            // Attach the fix to the last diagnostic reported for this clause
+            let element = cst.parent(clause).unwrap();
+            if let Some(last) = diagnostics.last_mut() {
+                last.fix = Some(Fix {
+                    edits: vec![TextEdit::delete(
+                        cst.start(element),
+                        semicolon_after(cst, cst.end(element)),
+                    )],
+                });
+            }
+        }
+    }
+}
+
+/// Return names imported by the given import clause. Wildcard imports
+/// are ignored, as it is impossible to determine what they introduce.
+fn imported_names(cst: &ModelicaCST, clause: TreeID) -> Vec<Import> {
+    let tokens = cst.tokens();
+    let mut imports = Vec::new();
+    let mut path = String::new();
+    let mut name = None;
+    let mut alias = None;
+    for child in cst.children(clause) {
+        match child {
+            Child::Tree(tree) => match cst.kind(*tree) {
+                SyntaxKind::Name => {
+                    path = text(cst, *tree);
+                    name = Some(*tree);
+                }
+                SyntaxKind::ImportList => {
+                    for child in cst.children(*tree) {
+                        if let Child::Token(tok) = child {
+                            if tokens.kind(*tok) == TokenKind::Identifier {
+                                imports.push(Import {
+                                    short: tokens.text(*tok).to_string(),
+                                    path: format!("{}.{}", path, tokens.text(*tok)),
+                                    token: *tok,
+                                });
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            },
+            Child::Token(tok) => match tokens.kind(*tok) {
+                TokenKind::Identifier => alias = Some(*tok),
+                TokenKind::DotStar => return Vec::new(),
+                _ => (),
+            },
+        }
+    }
+    if let (true, Some(name)) = (imports.is_empty(), name) {
+        let token = alias.unwrap_or_else(|| cst.end(name));
+        imports.push(Import {
+            short: tokens.text(token).to_string(),
+            path,
+            token,
+        });
+    }
+    imports
+}
+
+/// Collects import clauses of the class and first identifiers of all
+/// names and references used inside it, except those that are parts of
+/// import clauses. Names used in nested classes are also collected,
+/// whereas their import clauses are not.
+struct Scope {
+    class: TreeID,
+    clauses: Vec<TreeID>,
+    used: HashSet<String>,
+}
+
+impl Visitor for Scope {
+    fn enter(&mut self, cst: &ModelicaCST, kind: SyntaxKind, id: TreeID) -> bool {
+        match kind {
+            SyntaxKind::ImportClause => {
+                let nested = cst
+                    .ancestors(id)
+                    .find(|t| cst.kind(*t) == SyntaxKind::ClassDefinition);
+                if nested == Some(self.class) {
+                    self.clauses.push(id);
+                }
+                return false;
+            }
+            SyntaxKind::TypeSpecifier | SyntaxKind::ComponentReference => {
+                // Names preceded by a dot are looked up in the global scope
+                let first = cst.start(id);
+                if cst.tokens().kind(first) == TokenKind::Identifier {
+                    self.used.insert(cst.tokens().text(first).to_string());
+                }
+            }
+            SyntaxKind::Name
+                if cst.parent(id).map(|p| cst.kind(p)) != Some(SyntaxKind::TypeSpecifier) =>
+            {
+                self.used
+                    .insert(cst.tokens().text(cst.start(id)).to_string());
+            }
+            _ => (),
+        }
+        true
+    }
+}
+
+/// Return the concatenated text of all tokens in the node
+fn text(cst: &ModelicaCST, tree: TreeID) -> String {
+    cst.tokens_in(tree).map(|t| cst.tokens().text(t)).collect()
+}
+
+/// Return the semicolon that terminates the element
+fn semicolon_after(cst: &ModelicaCST, tok: TokenID) -> TokenID {
+    let mut next = tok;
+    while let Some(n) = cst.tokens().next(next) {
+        next = n;
+        if cst.tokens().kind(n) == TokenKind::Semicolon {
+            break;
+        }
+    }
+    next
+}
@@ -0,0 +1,162 @@
+use crate::parser::*;
+
+use crate::fixer::{Fix, TextEdit};
+
+use super::Diagnostic;
+
+/// Report `within` clauses and class names that don't match the location
+/// of the file in the library. `package` is the full name of the package
+/// that contains the file, and `class` is the name implied by the file
+/// name. Names that aren't valid Modelica names are not checked, as they
+/// couldn't be used as fixes.
+pub fn check(cst: &ModelicaCST, package: &str, class: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(root) = cst.root() else {
+        return;
+    };
+    let classes: Vec<TreeID> = cst
+        .children(root)
+        .iter()
+        .filter_map(|c| match c {
+            Child::Tree(t) if cst.kind(*t) == SyntaxKind::ClassDefinition => Some(*t),
+            _ => None,
+        })
+        .collect();
+    // Files with multiple classes can't be loaded anyway
+    let [definition] = classes.as_slice() else {
+        return;
+    };
+    if package.is_empty() || is_name(package) {
+        check_within(cst, root, package, diagnostics);
+    }
+    if is_name(class) && !class.contains('.') {
+        check_name(cst, *definition, class, diagnostics);
+    }
+}
+
+/// Return `true` if the text is a Modelica name, that is identifiers
+/// separated with dots
+fn is_name(text: &str) -> bool {
+    let cst = ModelicaCST::from(String::new(), String::from(text), EntryPoint::Name);
+    let tokens = cst.tokens();
+    tokens.errors().is_empty()
+        && cst.errors().is_empty()
+        && tokens
+            .all()
+            .into_iter()
+            .all(|t| matches!(tokens.kind(t), TokenKind::Identifier | TokenKind::Dot))
+}
+
+fn check_within(cst: &ModelicaCST, root: TreeID, package: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let tokens = cst.tokens();
+    let within = match cst.children(root).first() {
+        Some(Child::Token(tok)) if tokens.kind(*tok) == TokenKind::Within => *tok,
+        // Missing `within` clause is equivalent to the empty one
+        _ if package.is_empty() => return,
+        _ => {
+            let first = cst.start(root);
+            diagnostics.push(Diagnostic {
+                rule: "within-mismatch",
+                token: first,
+                msg: format!("missing within clause 'within {};'", package),
+                fix: Some(Fix {
+                    edits: vec![TextEdit::replace(
+                        first,
+                        first,
+                        format!("within {};\n{}", package, tokens.text(first)),
+                    )],
+                }),
+            });
+            return;
+        }
+    };
+    let name = cst.children(root).iter().find_map(|c| match c {
+        Child::Tree(t) if cst.kind(*t) == SyntaxKind::Name => Some(*t),
+        _ => None,
+    });
+    let path: String = name
+        .map(|n| cst.tokens_in(n).map(|t| tokens.text(t)).collect())
+        .unwrap_or_default();
+    if path == package {
+        return;
+    }
+    let (msg, edit) = match (name, package.is_empty()) {
+        (Some(name), false) => (
+            format!(
+                "within clause '{}' doesn't match the file location, expected '{}'",
+                path, package
+            ),
+            TextEdit::replace(cst.start(name), cst.end(name), String::from(package)),
+        ),
+        // Top-level classes don't need the `within` clause at all
+        (Some(name), true) => (
+            format!(
+                "within clause '{}' doesn't match the file location, expected the empty one",
+                path
+            ),
+            TextEdit::delete(within, tokens.next(cst.end(name)).unwrap_or(cst.end(name))),
+        ),
+        (None, _) => (
+            format!(
+                "empty within clause doesn't match the file location, expected '{}'",
+                package
+            ),
+            TextEdit::replace(within, within, format!("within {}", package)),
+        ),
+    };
+    diagnostics.push(Diagnostic {
+        rule: "within-mismatch",
+        token: name.map_or(within, |n| cst.start(n)),
+        msg,
+        fix: Some(Fix { edits: vec![edit] }),
+    });
+}
+
+fn check_name(
+    cst: &ModelicaCST,
+    definition: TreeID,
+    class: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let tokens = cst.tokens();
+    let Some(specifier) = cst.descendants(definition).find(|t| {
+        matches!(
+            cst.kind(*t),
+            SyntaxKind::LongClassSpecifier
+                | SyntaxKind::ShortClassSpecifier
+                | SyntaxKind::DerClassSpecifier
+        )
+    }) else {
+        return;
+    };
+    // Name of the class and the one after the `end` keyword
+    let names: Vec<TokenID> = cst
+        .children(specifier)
+        .iter()
+        .filter_map(|c| match c {
+            Child::Token(tok) if tokens.kind(*tok) == TokenKind::Identifier => Some(*tok),
+            _ => None,
+        })
+        .collect();
+    let Some(name) = names.first() else {
+        return;
+    };
+    if tokens.text(*name) == class {
+        return;
+    }
+    diagnostics.push(Diagnostic {
+        rule: "class-name-mismatch",
+        token: *name,
+        msg: format!(
+            "class name '{}' doesn't match the file name, expected '{}'",
+            tokens.text(*name),
+            class
+        ),
+        fix: Some(Fix {
+            edits: names
+                .iter()
+                .filter(|n| tokens.text(**n) == tokens.text(*name))
+                .map(|n| TextEdit::replace(*n, *n, String::from(class)))
+                .collect(),
+        }),
+    });
+}
@@ -1,4 +1,7 @@
-use mofmt::{ModelicaCST, SyntaxKind};
+use mofmt::{
+    check_package_order, merge_package, parse, parse_script, Diagnostic, EntryPoint, Fix,
+    FormatOptions, ModelicaCST, PackageFile, ParseErrors,
+};
 use std::io::{stdout, Write};
 use std::path::{Path, PathBuf};
 use std::{env, fs};
@@ -8,15 +11,41 @@ const VERSION: &str = "0.6.0";
 const HELP: &str = r#"
 mofmt: Modelica code formatter
 
-Usage: mofmt [OPTIONS] <PATHS>
+Usage: mofmt [OPTIONS] [FORMAT OPTIONS] <PATHS>
+       mofmt dump-cst [--json] <PATHS>
+       mofmt split [--force] <FILE> [<DIR>]
+       mofmt merge [FORMAT OPTIONS] <DIR> [<FILE>]
+       mofmt package-order [--write] <PATHS>
 
 Options:
 -h, --help: display this message and exit
 -v, --version: display a version number and exit
 --check: run mofmt in check mode (without modifying the file)
-"#;
+--lint: report lint diagnostics (without modifying the file)
+--fix: apply automatic fixes for lint diagnostics and format the file with FORMAT OPTIONS
+
+Format options:
+--html: reformat HTML in the Documentation annotation
+--align-matrices: align columns of matrices written in multiple lines
+--normalize-numbers: normalize spelling of real literals (like `1.E+3` to `1.0e3`)
+--max-blank-lines <N>: preserve at most N consecutive blank lines (default: 1)
+--no-section-blanks: don't insert blank lines around sections like `equation`
+--no-composition-blanks: don't insert blank lines after the class header and before `end`
+--class-blanks: insert blank lines before nested class definitions
+--normalize-comments: insert spaces after `//`, strip trailing whitespace and re-indent block comments
+--comment-width <N>: with --normalize-comments, wrap line comments that start a line and are longer than N characters
+--remove-bom: remove the byte order mark from the beginning of files
 
-const EOL: &str = if cfg!(windows) { "\r\n" } else { "\n" };
+Commands:
+dump-cst: print the syntax tree as an S-expression (or JSON with --json)
+split: store the package from the file as a directory inside DIR (by default
+       the directory containing the package) and remove the file; existing
+       files are overwritten only with --force
+merge: store the package from the directory in a single formatted FILE (by
+       default DIR with the .mo extension)
+package-order: check if package.order files list all classes and constants of
+       packages (or regenerate them with --write)
+"#;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -34,49 +63,128 @@ fn main() {
             eprintln!("Missing PATHS arguments.\n{}", HELP);
             std::process::exit(1);
         }
-        format_files(&args[2..], true);
-    } else if args[1].starts_with('-') {
-        eprintln!("Unrecognized option: '{}'.\n{}", args[1], HELP);
-        std::process::exit(1);
+        let (options, paths) = format_options(&args[2..]);
+        format_files(paths, &options, true);
+    } else if ["--lint", "--fix"].contains(&args[1].as_str()) {
+        if args.len() < 3 {
+            eprintln!("Missing PATHS arguments.\n{}", HELP);
+            std::process::exit(1);
+        }
+        let (options, paths) = format_options(&args[2..]);
+        lint_files(paths, &options, args[1].as_str() == "--fix");
+    } else if args[1].as_str() == "dump-cst" {
+        let json = args.get(2).is_some_and(|a| a == "--json");
+        let paths = if json { &args[3..] } else { &args[2..] };
+        if paths.is_empty() {
+            eprintln!("Missing PATHS arguments.\n{}", HELP);
+            std::process::exit(1);
+        }
+        dump_files(paths, json);
+    } else if args[1].as_str() == "split" {
+        let force = args.get(2).is_some_and(|a| a == "--force");
+        let paths = if force { &args[3..] } else { &args[2..] };
+        match paths {
+            [file] => split_file(Path::new(file), None, force),
+            [file, dir] => split_file(Path::new(file), Some(Path::new(dir)), force),
+            _ => {
+                eprintln!("Expected FILE and optional DIR arguments.\n{}", HELP);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1].as_str() == "merge" {
+        let (options, paths) = format_options(&args[2..]);
+        match paths {
+            [dir] => merge_dir(Path::new(dir), &merged_file(Path::new(dir)), &options),
+            [dir, file] => merge_dir(Path::new(dir), Path::new(file), &options),
+            _ => {
+                eprintln!("Expected DIR and optional FILE arguments.\n{}", HELP);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1].as_str() == "package-order" {
+        let write = args.get(2).is_some_and(|a| a == "--write");
+        let paths = if write { &args[3..] } else { &args[2..] };
+        if paths.is_empty() {
+            eprintln!("Missing PATHS arguments.\n{}", HELP);
+            std::process::exit(1);
+        }
+        check_order_files(paths, write);
     } else {
-        format_files(&args[1..], false);
+        let (options, paths) = format_options(&args[1..]);
+        if paths.is_empty() {
+            eprintln!("Missing PATHS arguments.\n{}", HELP);
+            std::process::exit(1);
+        }
+        format_files(paths, &options, false);
     }
 }
 
+/// Return formatting options that precede paths in the argument list,
+/// and the remaining arguments
+fn format_options(args: &[String]) -> (FormatOptions, &[String]) {
+    let mut options = FormatOptions::new();
+    let mut rest = args;
+    while let Some(arg) = rest.first().filter(|a| a.starts_with('-')) {
+        match arg.as_str() {
+            "--html" => options.html = true,
+            "--align-matrices" => options.align_matrices = true,
+            "--normalize-numbers" => options.normalize_numbers = true,
+            "--max-blank-lines" => {
+                rest = &rest[1..];
+                match rest.first().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.max_blank_lines = n,
+                    None => {
+                        eprintln!("Option '--max-blank-lines' requires a number.\n{}", HELP);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--no-section-blanks" => options.blank_lines_around_sections = false,
+            "--no-composition-blanks" => options.blank_lines_in_composition = false,
+            "--class-blanks" => options.blank_line_before_classes = true,
+            "--normalize-comments" => options.normalize_comments = true,
+            "--remove-bom" => options.remove_bom = true,
+            "--comment-width" => {
+                rest = &rest[1..];
+                match rest.first().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.comment_width = Some(n),
+                    None => {
+                        eprintln!("Option '--comment-width' requires a number.\n{}", HELP);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => {
+                eprintln!("Unrecognized option: '{}'.\n{}", arg, HELP);
+                std::process::exit(1);
+            }
+        }
+        rest = &rest[1..];
+    }
+    (options, rest)
+}
+
 /// Format files specified in the argument list
-fn format_files(args: &[String], check: bool) {
+fn format_files(args: &[String], options: &FormatOptions, check: bool) {
     let mut code = 0;
-    let mut files = Vec::new();
+    let files = collect_files(args);
     let mut lock = stdout().lock();
-    args.iter()
-        .map(PathBuf::from)
-        .map(|p| {
-            if p.is_dir() {
-                get_files_from_dir(p)
-            } else {
-                vec![p]
-            }
-        })
-        .for_each(|mut v| files.append(&mut v));
     files.iter().for_each(|p| {
         let contents = read_file(p);
         let name = p.display();
         match contents {
-            Ok(source) => {
-                let parsed = ModelicaCST::from(name.to_string(), source, SyntaxKind::StoredDefinition);
-                let mut errors = parsed.tokens().errors();
-                errors.append(&mut parsed.errors());
-                if !errors.is_empty() {
+            Ok(source) => match parse_file(p, &source) {
+                Err(errors) => {
                     writeln!(
                         lock,
                         "\n{}: \x1b[31msyntax errors detected\x1b[0m\n{}",
-                        name,
-                        errors.join("\n")
+                        name, errors
                     )
                     .unwrap();
                     code = 1;
-                } else {
-                    let output = parsed.pretty_print() + EOL;
+                }
+                Ok(parsed) => {
+                    let output = parsed.pretty_print_with(options);
                     if check {
                         if output != parsed.tokens().code() {
                             code = 1;
@@ -88,6 +196,141 @@ fn format_files(args: &[String], check: bool) {
                         write_file(p, output);
                     }
                 }
+            },
+            Err(e) => {
+                eprintln!("{}: error: {}", name, e);
+                code = 1;
+            }
+        }
+    });
+    std::process::exit(code);
+}
+
+/// Return lint diagnostics for the file, including problems with its
+/// location in the library
+fn lint_file(path: &Path, cst: &ModelicaCST) -> Vec<Diagnostic> {
+    let mut diagnostics = cst.lint();
+    if let Some((package, class)) = library_location(path) {
+        diagnostics.append(&mut cst.lint_location(&package, &class));
+        diagnostics.sort_by_key(|d| d.token);
+    }
+    diagnostics
+}
+
+/// Return the full name of the package that contains the file and the
+/// name of the class implied by the file location. Return `None` if the
+/// file is not a part of the package stored as a directory hierarchy.
+/// Version that follows the name of the library directory, like in
+/// `Modelica 4.0.0`, is ignored.
+fn library_location(path: &Path) -> Option<(String, String)> {
+    if is_script(path) {
+        return None;
+    }
+    let path = fs::canonicalize(path).ok()?;
+    let mut dir = path.parent()?;
+    let mut class = if path.file_name()? == "package.mo" {
+        let class = dir.file_name()?.to_str()?;
+        dir = dir.parent()?;
+        class
+    } else if dir.join("package.mo").is_file() {
+        path.file_stem()?.to_str()?
+    } else {
+        return None;
+    };
+    // Library root is the topmost directory containing `package.mo`
+    let mut packages = Vec::new();
+    while dir.join("package.mo").is_file() {
+        packages.push(dir.file_name()?.to_str()?);
+        dir = dir.parent()?;
+    }
+    packages.reverse();
+    let top = packages.first_mut().unwrap_or(&mut class);
+    *top = top.split_once(' ').map_or(*top, |(name, _)| name);
+    Some((packages.join("."), String::from(class)))
+}
+
+/// Report lint diagnostics for files specified in the argument list.
+/// If `fix` is `true`, apply available fixes and format files with the
+/// given options.
+fn lint_files(args: &[String], options: &FormatOptions, fix: bool) {
+    let mut code = 0;
+    let files = collect_files(args);
+    let mut lock = stdout().lock();
+    files.iter().for_each(|p| {
+        let contents = read_file(p);
+        let name = p.display();
+        match contents {
+            Ok(source) => match parse_file(p, &source) {
+                Err(errors) => {
+                    writeln!(
+                        lock,
+                        "\n{}: \x1b[31msyntax errors detected\x1b[0m\n{}",
+                        name, errors
+                    )
+                    .unwrap();
+                    code = 1;
+                }
+                Ok(parsed) => {
+                    let mut diagnostics = lint_file(p, &parsed);
+                    if fix {
+                        let fixes: Vec<&Fix> =
+                            diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+                        let (output, applied) = parsed.apply_fixes(&fixes, options);
+                        if applied > 0 {
+                            writeln!(lock, "{}: applied {} fixes", name, applied).unwrap();
+                        }
+                        let fixed =
+                            ModelicaCST::from(name.to_string(), output.clone(), entry_point(p));
+                        diagnostics = lint_file(p, &fixed);
+                        diagnostics
+                            .iter()
+                            .for_each(|d| writeln!(lock, "{}", d.message(fixed.tokens())).unwrap());
+                        if !diagnostics.is_empty() {
+                            code = 1;
+                        }
+                        write_file(p, output);
+                        return;
+                    }
+                    if !diagnostics.is_empty() {
+                        code = 1;
+                    }
+                    diagnostics
+                        .iter()
+                        .for_each(|d| writeln!(lock, "{}", d.message(parsed.tokens())).unwrap());
+                }
+            },
+            Err(e) => {
+                eprintln!("{}: error: {}", name, e);
+                code = 1;
+            }
+        }
+    });
+    std::process::exit(code);
+}
+
+/// Print syntax trees of files specified in the argument list
+fn dump_files(args: &[String], json: bool) {
+    let mut code = 0;
+    let files = collect_files(args);
+    let mut lock = stdout().lock();
+    files.iter().for_each(|p| {
+        let contents = read_file(p);
+        let name = p.display();
+        match contents {
+            Ok(source) => {
+                let parsed = ModelicaCST::from(name.to_string(), source, entry_point(p));
+                let mut errors = parsed.tokens().errors();
+                errors.append(&mut parsed.errors());
+                if !errors.is_empty() {
+                    eprintln!("{}", errors.join("\n"));
+                    code = 1;
+                }
+                let output = if json {
+                    parsed.to_json()
+                } else {
+                    parsed.to_sexpr()
+                };
+                writeln!(lock, "{}", output).unwrap();
             }
             Err(e) => {
                 eprintln!("{}: error: {}", name, e);
@@ -98,6 +341,236 @@ fn format_files(args: &[String], check: bool) {
     std::process::exit(code);
 }
 
+/// Store the package from the file as a directory hierarchy. Files are
+/// written to the given directory, or to the directory that contains
+/// the package directory or file.
+fn split_file(path: &Path, dir: Option<&Path>, force: bool) {
+    let name = path.display();
+    let parsed = match read_file(path) {
+        Ok(source) => parse_file(path, &source),
+        Err(e) => {
+            eprintln!("{}: error: {}", name, e);
+            std::process::exit(1);
+        }
+    };
+    let files = match parsed.map(|cst| cst.split_package()) {
+        Ok(Ok(files)) => files,
+        Ok(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Err(errors) => {
+            eprintln!("{}: \x1b[31msyntax errors detected\x1b[0m\n{}", name, errors);
+            std::process::exit(1);
+        }
+    };
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let dir = dir.unwrap_or(if path.file_name().is_some_and(|f| f == "package.mo") {
+        parent.parent().unwrap_or(Path::new("."))
+    } else {
+        parent
+    });
+    let source = fs::canonicalize(path).ok();
+    let is_source = |to: &Path| source.is_some() && fs::canonicalize(to).ok() == source;
+    // The split file itself may be replaced by the generated package.mo
+    let existing: Vec<PathBuf> = files
+        .iter()
+        .map(|f| dir.join(&f.path))
+        .filter(|to| to.exists() && !is_source(to))
+        .collect();
+    if !force && !existing.is_empty() {
+        for to in existing {
+            eprintln!("{}: error: file already exists", to.display());
+        }
+        eprintln!("Use --force to overwrite existing files.");
+        std::process::exit(1);
+    }
+    let mut replaced = false;
+    for file in files {
+        let to = dir.join(file.path);
+        replaced |= is_source(&to);
+        let written = match to.parent() {
+            Some(parent) => fs::create_dir_all(parent).and_then(|_| fs::write(&to, file.code)),
+            None => fs::write(&to, file.code),
+        };
+        if let Err(e) = written {
+            eprintln!("{}: error: {}", to.display(), e);
+            std::process::exit(1);
+        }
+    }
+    // The split file is removed only after all files were written,
+    // unless it was replaced by the generated package.mo, so the
+    // package isn't stored twice
+    if !replaced {
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("{}: error: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Return the default path of the file with the package merged from
+/// the directory. The path is resolved first, so directories like `.`
+/// get the name of the package.
+fn merged_file(dir: &Path) -> PathBuf {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    match dir.file_name() {
+        Some(name) => dir.with_file_name(format!("{}.mo", name.to_string_lossy())),
+        None => dir.with_extension("mo"),
+    }
+}
+
+/// Store the package from the directory hierarchy in a single file
+fn merge_dir(dir: &Path, to: &Path, options: &FormatOptions) {
+    if !dir.is_dir() {
+        eprintln!("{}: error: not a directory", dir.display());
+        std::process::exit(1);
+    }
+    let files: Vec<PackageFile> = get_files_from_dir(dir.to_path_buf())
+        .into_iter()
+        .chain(get_order_files(dir))
+        .map(|p| PackageFile {
+            code: fs::read_to_string(&p)
+                .unwrap_or_else(|_| panic!("{}: error reading a file", p.display())),
+            path: p.strip_prefix(dir).unwrap().to_path_buf(),
+        })
+        .collect();
+    match merge_package(&files, options) {
+        Ok(code) => write_file(to, code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Check `package.order` files in package directories found in paths
+/// specified in the argument list. If `write` is `true`, regenerate
+/// invalid files.
+fn check_order_files(args: &[String], write: bool) {
+    let mut code = 0;
+    let mut lock = stdout().lock();
+    let mut dirs = Vec::new();
+    for arg in args {
+        let path = Path::new(arg);
+        if path.is_dir() {
+            dirs.append(&mut get_package_dirs(path));
+        } else {
+            eprintln!("{}: error: not a directory", path.display());
+            code = 1;
+        }
+    }
+    for dir in dirs {
+        let path = dir.join("package.order");
+        let name = path.display();
+        let files = package_files(&dir);
+        let order = match check_package_order(&files) {
+            Ok(order) => order,
+            Err(e) => {
+                // Paths in errors are relative to the package directory
+                eprintln!("{}: {}", dir.display(), e);
+                code = 1;
+                continue;
+            }
+        };
+        if order.missing.is_empty() && order.extra.is_empty() {
+            continue;
+        }
+        if write {
+            write_file(&path, order.code);
+            writeln!(lock, "{}: regenerated", name).unwrap();
+            continue;
+        }
+        code = 1;
+        for entry in order.missing {
+            writeln!(lock, "{}: missing '{}'", name, entry).unwrap();
+        }
+        for entry in order.extra {
+            writeln!(lock, "{}: extra '{}'", name, entry).unwrap();
+        }
+    }
+    std::process::exit(code);
+}
+
+/// Return all package directories inside the given path, including
+/// the path itself
+fn get_package_dirs(path: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if !path.is_dir() {
+        return dirs;
+    }
+    if path.join("package.mo").is_file() {
+        dirs.push(path.to_path_buf());
+    }
+    let paths = fs::read_dir(path)
+        .unwrap_or_else(|_| panic!("{}: error reading from a directory", path.display()));
+    for p in paths.map(|e| e.unwrap().path()) {
+        dirs.append(&mut get_package_dirs(&p));
+    }
+    dirs
+}
+
+/// Return files describing contents of the package directory. Only
+/// `package.mo` and `package.order` files are read.
+fn package_files(dir: &Path) -> Vec<PackageFile> {
+    let mut files = Vec::new();
+    for name in ["package.mo", "package.order"] {
+        if let Ok(code) = fs::read_to_string(dir.join(name)) {
+            files.push(PackageFile {
+                path: PathBuf::from(name),
+                code,
+            });
+        }
+    }
+    let paths = fs::read_dir(dir)
+        .unwrap_or_else(|_| panic!("{}: error reading from a directory", dir.display()));
+    for p in paths.map(|e| e.unwrap().path()) {
+        let path = if p.join("package.mo").is_file() {
+            p.join("package.mo")
+        } else if is_modelica(&p) && !is_script(&p) {
+            p.clone()
+        } else {
+            continue;
+        };
+        files.push(PackageFile {
+            path: path.strip_prefix(dir).unwrap().to_path_buf(),
+            code: String::new(),
+        });
+    }
+    files
+}
+
+/// Return all `package.order` files from the given directory
+fn get_order_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let paths = fs::read_dir(dir)
+        .unwrap_or_else(|_| panic!("{}: error reading from a directory", dir.display()));
+    for path in paths.map(|e| e.unwrap().path()) {
+        if path.is_dir() {
+            files.append(&mut get_order_files(&path));
+        } else if path.file_name().is_some_and(|f| f == "package.order") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Return all Modelica files from paths specified in the argument list
+fn collect_files(args: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    args.iter()
+        .map(PathBuf::from)
+        .map(|p| {
+            if p.is_dir() {
+                get_files_from_dir(p)
+            } else {
+                vec![p]
+            }
+        })
+        .for_each(|mut v| files.append(&mut v));
+    files
+}
+
 /// Return all Modelica files from the given directory
 fn get_files_from_dir(dir: PathBuf) -> Vec<PathBuf> {
     let mut files = Vec::new();
@@ -119,14 +592,38 @@ fn get_files_from_dir(dir: PathBuf) -> Vec<PathBuf> {
     files
 }
 
-/// Return `true` if the file is a Modelica file
+/// Return `true` if the file is a Modelica file or a Modelica script
 fn is_modelica(f: &Path) -> bool {
     if let Some(suffix) = f.extension() {
-        return suffix == "mo";
+        return suffix == "mo" || suffix == "mos";
     }
     false
 }
 
+/// Return `true` if the file is a Modelica script
+fn is_script(f: &Path) -> bool {
+    f.extension().is_some_and(|s| s == "mos")
+}
+
+/// Return the grammar rule used to parse the file
+fn entry_point(f: &Path) -> EntryPoint {
+    if is_script(f) {
+        EntryPoint::Script
+    } else {
+        EntryPoint::StoredDefinition
+    }
+}
+
+/// Parse contents of the Modelica file or script
+fn parse_file(f: &Path, source: &str) -> Result<ModelicaCST, ParseErrors> {
+    let name = f.display().to_string();
+    if is_script(f) {
+        parse_script(source, &name)
+    } else {
+        parse(source, &name)
+    }
+}
+
 /// Return contents of the Modelica file
 fn read_file(from: &Path) -> Result<String, String> {
     if !is_modelica(from) {
@@ -142,3 +639,25 @@ fn read_file(from: &Path) -> Result<String, String> {
 fn write_file(to: &Path, code: String) {
     fs::write(to, code).unwrap_or_else(|_| panic!("{}: error writing a file", to.display()));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_location() {
+        let root = env::temp_dir().join(format!("mofmt-location-{}", std::process::id()));
+        let lib = root.join("Lib 1.0");
+        fs::create_dir_all(lib.join("Sub")).unwrap();
+        for file in ["package.mo", "A.mo", "Sub/package.mo", "Sub/B.mo"] {
+            fs::write(lib.join(file), "").unwrap();
+        }
+        let location = |file: &str| library_location(&lib.join(file));
+        let expected = |package: &str, class: &str| Some((package.into(), class.into()));
+        assert_eq!(location("package.mo"), expected("", "Lib"));
+        assert_eq!(location("A.mo"), expected("Lib", "A"));
+        assert_eq!(location("Sub/package.mo"), expected("Lib", "Sub"));
+        assert_eq!(location("Sub/B.mo"), expected("Lib.Sub", "B"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
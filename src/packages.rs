@@ -0,0 +1,798 @@
+//! Conversions between packages stored in single files and packages
+//! stored as directory hierarchies.
+//!
+//! Classes moved between files keep their original source code,
+//! including comments. Only the indentation of nested classes is
+//! adjusted.
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::formatter::FormatOptions;
+use crate::parser::ast::{AstNode, ClassDefinition, Element, StoredDefinition};
+use crate::parser::*;
+
+/// Represents a file generated from the package.
+///
+/// # Fields
+/// - `path`: path of the file relative to the output directory
+/// - `code`: contents of the file
+#[derive(Debug, PartialEq)]
+pub struct PackageFile {
+    pub path: PathBuf,
+    pub code: String,
+}
+
+/// Class that is moved out of the package
+struct Nested {
+    name: String,
+    is_package: bool,
+    /// Range of the class code together with its comments
+    code: Range<usize>,
+    /// Range of lines removed from the package
+    lines: Range<usize>,
+}
+
+impl ModelicaCST {
+    /// Split the package stored in the file into the directory hierarchy.
+    ///
+    /// Every public class defined in the package is moved into a
+    /// separate file, or into a directory if it is a package itself.
+    /// Each directory gets `package.mo` with the remaining code and
+    /// `package.order` that lists all classes and constants defined in
+    /// the package in their original order.
+    pub fn split_package(&self) -> Result<Vec<PackageFile>, String> {
+        let tokens = self.tokens();
+        let root = self
+            .root()
+            .and_then(|r| StoredDefinition::cast(self, r))
+            .ok_or_else(|| format!("{}: file doesn't contain a class", tokens.source()))?;
+        let package = match root.class_definitions().as_slice() {
+            [class] if is_package(class) => *class,
+            _ => {
+                return Err(format!(
+                    "{}: file must contain a single package",
+                    tokens.source()
+                ))
+            }
+        };
+        let name = package.name().unwrap_or_default();
+        let within = match root.within() {
+            Some(within) => format!("{}.{}", within.text(), name),
+            None => String::from(name),
+        };
+        let code = tokens.code();
+        let eol = if code.contains("\r\n") { "\r\n" } else { "\n" };
+        let nested = nested_classes(self, package);
+        let mut files = Vec::new();
+        let mut remaining = String::new();
+        let order: String = defined_names(self).into_iter().map(|n| n + eol).collect();
+        let mut pos = 0;
+        for class in nested {
+            remaining.push_str(&code[pos..class.lines.start]);
+            pos = class.lines.end;
+            let class_code = format!(
+                "within {};{}{}{}",
+                within,
+                eol,
+                dedent(tokens, class.code.clone()),
+                eol
+            );
+            if class.is_package {
+                let cst = ModelicaCST::from(
+                    format!("{}/{}/package.mo", name, class.name),
+                    class_code,
+                    EntryPoint::StoredDefinition,
+                );
+                for file in cst.split_package()? {
+                    files.push(PackageFile {
+                        path: PathBuf::from(name).join(file.path),
+                        code: file.code,
+                    });
+                }
+            } else {
+                files.push(PackageFile {
+                    path: PathBuf::from(name).join(format!("{}.mo", class.name)),
+                    code: class_code,
+                });
+            }
+        }
+        remaining.push_str(&code[pos..]);
+        files.insert(
+            0,
+            PackageFile {
+                path: PathBuf::from(name).join("package.mo"),
+                code: remaining,
+            },
+        );
+        if !order.is_empty() {
+            files.insert(
+                1,
+                PackageFile {
+                    path: PathBuf::from(name).join("package.order"),
+                    code: order,
+                },
+            );
+        }
+        Ok(files)
+    }
+}
+
+/// Return `true` if the class is a package defined with the long class
+/// specifier
+fn is_package(class: &ClassDefinition) -> bool {
+    class.prefixes().and_then(|p| p.restriction()) == Some(TokenKind::Package)
+        && class.end_name().is_some()
+}
+
+/// Return public classes defined in the package that can be moved to
+/// separate files. Classes with element prefixes like `replaceable` are
+/// left in place.
+fn nested_classes(cst: &ModelicaCST, package: ClassDefinition) -> Vec<Nested> {
+    let mut nested = Vec::new();
+    let Some(composition) = package.composition() else {
+        return nested;
+    };
+    let mut public = true;
+    for child in cst.children(composition.syntax()) {
+        let list = match child {
+            Child::Token(tok) => {
+                match cst.tokens().kind(*tok) {
+                    TokenKind::Public => public = true,
+                    TokenKind::Protected => public = false,
+                    _ => (),
+                }
+                continue;
+            }
+            Child::Tree(tree) if public && cst.kind(*tree) == SyntaxKind::ElementList => *tree,
+            Child::Tree(_) => continue,
+        };
+        let mut children = cst.children(list).iter().peekable();
+        while let Some(child) = children.next() {
+            let (Child::Tree(element), Some(Child::Token(semicolon))) = (child, children.peek())
+            else {
+                continue;
+            };
+            let class = match cst.children(*element) {
+                [Child::Tree(tree)] => ClassDefinition::cast(cst, *tree),
+                _ => None,
+            };
+            if let Some(class) = class {
+                let code = class_range(cst.tokens(), cst.start(*element), *semicolon);
+                nested.push(Nested {
+                    name: String::from(class.name().unwrap_or_default()),
+                    is_package: is_package(&class),
+                    lines: line_range(cst.tokens().code(), code.clone()),
+                    code,
+                });
+            }
+        }
+    }
+    nested
+}
+
+/// Return the range of the class code extended with comments directly
+/// preceding it and comments that follow it in the same line
+fn class_range(tokens: &Tokens, first: TokenID, semicolon: TokenID) -> Range<usize> {
+    let start = code_start(tokens, first);
+    let end = tokens
+        .trailing_trivia(semicolon)
+        .iter()
+        .rev()
+        .find_map(|t| match t {
+            Trivia::Token(comment) => Some(tokens.range(*comment).end),
+            Trivia::Whitespace(_) => None,
+        })
+        .unwrap_or(tokens.range(semicolon).end);
+    start..end
+}
+
+/// Return the start of the code beginning with the token, extended with
+/// comments directly preceding it
+fn code_start(tokens: &Tokens, first: TokenID) -> usize {
+    let code = tokens.code();
+    let mut start = tokens.range(first).start;
+    for trivia in tokens.leading_trivia(first).iter().rev() {
+        match trivia {
+            Trivia::Whitespace(range) if code[range.clone()].matches('\n').count() > 1 => break,
+            Trivia::Whitespace(_) => (),
+            Trivia::Token(comment) => start = tokens.range(*comment).start,
+        }
+    }
+    start
+}
+
+/// Return the range of lines occupied by the code, including the
+/// blank lines that follow it if it's preceded by a blank line
+fn line_range(code: &str, range: Range<usize>) -> Range<usize> {
+    let start = code[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let mut end = code[range.end..]
+        .find('\n')
+        .map_or(code.len(), |i| range.end + i + 1);
+    let prev = &code[..start.saturating_sub(1)];
+    let prev_line = &prev[prev.rfind('\n').map_or(0, |i| i + 1)..];
+    if prev_line.trim().is_empty() {
+        while let Some(i) = code[end..].find('\n') {
+            if !code[end..end + i].trim().is_empty() {
+                break;
+            }
+            end += i + 1;
+        }
+    }
+    start..end
+}
+
+/// Return the code with continuation lines shifted left by the
+/// indentation of its first line. Lines that start inside string
+/// literals are not modified.
+fn dedent(tokens: &Tokens, range: Range<usize>) -> String {
+    let code = tokens.code();
+    let line_start = code[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &code[line_start..range.start];
+    let strings: Vec<Range<usize>> = tokens
+        .all()
+        .into_iter()
+        .filter(|t| tokens.kind(*t) == TokenKind::String)
+        .map(|t| tokens.range(t))
+        .filter(|r| r.start < range.end && range.start < r.end)
+        .collect();
+    let mut out = String::new();
+    let mut pos = range.start;
+    for line in code[range.clone()].split_inclusive('\n') {
+        let in_string = strings.iter().any(|r| r.start < pos && pos < r.end);
+        match line.strip_prefix(indent) {
+            Some(rest) if pos > range.start && !in_string => out.push_str(rest),
+            _ => out.push_str(line),
+        }
+        pos += line.len();
+    }
+    out
+}
+
+/// Merge the package stored as a directory hierarchy into a single
+/// file and return its formatted code.
+///
+/// Paths of the files have to be relative to the package directory.
+/// Nested classes are placed in the public part of the enclosing
+/// package, in the order given by `package.order`, followed by classes
+/// missing from it in alphabetical order. Each class is inserted before
+/// the first element of `package.mo` that is listed after it, so the
+/// elements defined in `package.mo` keep their relative order. Elements
+/// that follow a `public` or `protected` keyword are not considered.
+pub fn merge_package(files: &[PackageFile], options: &FormatOptions) -> Result<String, String> {
+    let code = merge_dir(files, Path::new(""))?;
+    let cst = parse(&code, "package.mo").map_err(|e| e.to_string())?;
+    Ok(cst.pretty_print_with(options))
+}
+
+/// Return the code of the package from the directory with nested
+/// classes inserted
+fn merge_dir(files: &[PackageFile], dir: &Path) -> Result<String, String> {
+    let path = dir.join("package.mo");
+    let code =
+        find_file(files, &path).ok_or_else(|| format!("{}: file doesn't exist", path.display()))?;
+    let cst = parse(code, &path.display().to_string()).map_err(|e| e.to_string())?;
+    let classes = cst
+        .root()
+        .and_then(|r| StoredDefinition::cast(&cst, r))
+        .map(|r| r.class_definitions())
+        .unwrap_or_default();
+    let package = match classes.as_slice() {
+        [class] if is_package(class) => *class,
+        _ => {
+            return Err(format!(
+                "{}: file must contain a single package",
+                path.display()
+            ))
+        }
+    };
+    let tokens = cst.tokens();
+    let order = find_file(files, &dir.join("package.order")).unwrap_or_default();
+    let position = |name: &str| order.lines().map(str::trim).position(|n| n == name);
+    // Elements of the package paired with the earliest position of
+    // their names in `package.order`
+    let listed: Vec<(usize, usize)> = public_elements(&cst, package)
+        .iter()
+        .filter_map(|(element, first)| {
+            let i = element_names(element).iter().filter_map(|n| position(n)).min()?;
+            Some((i, code_start(tokens, *first)))
+        })
+        .collect();
+    let end = tokens.range(insertion_point(&cst, package)).start;
+    // Classes grouped by the offset they are inserted at. Offsets never
+    // decrease, as the classes are sorted by `package.order`.
+    let mut inserted: Vec<(usize, String)> = Vec::new();
+    for (name, is_package) in package_classes(files, dir)? {
+        let mut class = if is_package {
+            let nested = dir.join(&name);
+            let code = merge_dir(files, &nested)?;
+            class_code(&nested.join("package.mo"), &code)?
+        } else {
+            let path = dir.join(format!("{}.mo", name));
+            class_code(&path, find_file(files, &path).unwrap_or_default())?
+        };
+        class.push('\n');
+        let anchor = position(&name)
+            .and_then(|i| listed.iter().find(|(j, _)| *j > i))
+            .map_or(end, |(_, start)| *start);
+        match inserted.last_mut() {
+            Some((offset, classes)) if *offset == anchor => classes.push_str(&class),
+            _ => inserted.push((anchor, class)),
+        }
+    }
+    let mut merged = String::from(code);
+    for (anchor, classes) in inserted.iter().rev() {
+        let line_start = code[..*anchor].rfind('\n').map_or(0, |i| i + 1);
+        if code[line_start..*anchor].trim().is_empty() {
+            merged.insert_str(line_start, classes);
+        } else {
+            merged.insert_str(*anchor, &format!("\n{}", classes));
+        }
+    }
+    Ok(merged)
+}
+
+/// Return the contents of the file of the given path
+fn find_file<'a>(files: &'a [PackageFile], path: &Path) -> Option<&'a str> {
+    files
+        .iter()
+        .find(|f| f.path == path)
+        .map(|f| f.code.as_str())
+}
+
+/// Return names of classes stored in the package directory in the
+/// order in which they should be merged. Packages are marked with
+/// `true`.
+fn package_classes(files: &[PackageFile], dir: &Path) -> Result<Vec<(String, bool)>, String> {
+    let order = find_file(files, &dir.join("package.order")).unwrap_or_default();
+    Ok(sort_by_order(order, stored_classes(files, dir)?, |(name, _)| name))
+}
+
+/// Return names of classes stored in separate files in the package
+/// directory in alphabetical order. Packages are marked with `true`.
+/// Subdirectories that contain Modelica files but no `package.mo` are
+/// reported as errors, as their classes would be lost otherwise.
+fn stored_classes(files: &[PackageFile], dir: &Path) -> Result<Vec<(String, bool)>, String> {
+    let mut classes: Vec<(String, bool)> = Vec::new();
+    for file in files {
+        let Ok(rest) = file.path.strip_prefix(dir) else {
+            continue;
+        };
+        let parts: Vec<&str> = rest.iter().filter_map(|p| p.to_str()).collect();
+        let class = match parts.as_slice() {
+            [name, "package.mo"] => (String::from(*name), true),
+            [file] if *file != "package.mo" && file.ends_with(".mo") => {
+                (String::from(file.trim_end_matches(".mo")), false)
+            }
+            [name, .., last] if last.ends_with(".mo") => {
+                let path = dir.join(name).join("package.mo");
+                if find_file(files, &path).is_none() {
+                    return Err(format!("{}: file doesn't exist", path.display()));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        classes.push(class);
+    }
+    classes.sort();
+    Ok(classes)
+}
+
+/// Return items sorted in the order of names listed in the
+/// `package.order` file. Items that are not listed are placed at the
+/// end in the original order.
+fn sort_by_order<T>(order: &str, mut items: Vec<T>, name: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut ordered = Vec::new();
+    for listed in order.lines().map(str::trim) {
+        if let Some(i) = items.iter().position(|item| name(item) == listed) {
+            ordered.push(items.remove(i));
+        }
+    }
+    ordered.append(&mut items);
+    ordered
+}
+
+/// Represents the result of the `package.order` file validation.
+///
+/// # Fields
+/// - `missing`: classes and constants that are not listed in the file
+/// - `extra`: listed names that don't refer to any class or constant of
+///   the package, or that are listed more than once
+/// - `code`: contents of the regenerated file
+#[derive(Debug, PartialEq)]
+pub struct PackageOrder {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub code: String,
+}
+
+/// Check if `package.order` lists exactly the classes and constants
+/// defined in `package.mo` together with classes stored in separate
+/// files and directories.
+///
+/// Paths of the files have to be relative to the package directory.
+/// The regenerated file keeps the existing order of entries, and the
+/// missing ones are appended in the order of their definitions,
+/// followed by classes stored in separate files.
+pub fn check_package_order(files: &[PackageFile]) -> Result<PackageOrder, String> {
+    let dir = Path::new("");
+    let path = dir.join("package.mo");
+    let code =
+        find_file(files, &path).ok_or_else(|| format!("{}: file doesn't exist", path.display()))?;
+    let cst = parse(code, &path.display().to_string()).map_err(|e| e.to_string())?;
+    let mut names = defined_names(&cst);
+    names.extend(stored_classes(files, dir)?.into_iter().map(|(name, _)| name));
+    let order = find_file(files, &dir.join("package.order")).unwrap_or_default();
+    let mut extra = Vec::new();
+    let mut listed: Vec<&str> = Vec::new();
+    for entry in order.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if listed.contains(&entry) || !names.iter().any(|n| n == entry) {
+            extra.push(String::from(entry));
+        } else {
+            listed.push(entry);
+        }
+    }
+    let missing = names
+        .iter()
+        .filter(|n| !listed.contains(&n.as_str()))
+        .cloned()
+        .collect();
+    let eol = if order.contains("\r\n") { "\r\n" } else { "\n" };
+    let code = sort_by_order(order, names, |n| n)
+        .into_iter()
+        .map(|n| n + eol)
+        .collect();
+    Ok(PackageOrder {
+        missing,
+        extra,
+        code,
+    })
+}
+
+/// Return names of classes and constants defined in the package
+fn defined_names(cst: &ModelicaCST) -> Vec<String> {
+    let classes = cst
+        .root()
+        .and_then(|r| StoredDefinition::cast(cst, r))
+        .map(|r| r.class_definitions())
+        .unwrap_or_default();
+    let Some(composition) = classes.first().and_then(|c| c.composition()) else {
+        return Vec::new();
+    };
+    composition.elements().iter().flat_map(element_names).collect()
+}
+
+/// Return names of classes and constants defined by the element
+fn element_names(element: &Element) -> Vec<String> {
+    if let Some(class) = element.class_definition() {
+        return class.name().map(String::from).into_iter().collect();
+    }
+    let Some(component) = element.component_clause() else {
+        return Vec::new();
+    };
+    let is_constant = component
+        .prefixes()
+        .is_some_and(|p| p.kinds().contains(&TokenKind::Constant));
+    if !is_constant {
+        return Vec::new();
+    }
+    component
+        .declarations()
+        .iter()
+        .filter_map(|d| d.name().map(String::from))
+        .collect()
+}
+
+/// Return elements of the package that precede the insertion point of
+/// nested classes, paired with their first tokens
+fn public_elements<'a>(
+    cst: &'a ModelicaCST,
+    package: ClassDefinition<'a>,
+) -> Vec<(Element<'a>, TokenID)> {
+    let list = package
+        .composition()
+        .and_then(|c| cst.children(c.syntax()).first())
+        .and_then(|child| match child {
+            Child::Tree(tree) if cst.kind(*tree) == SyntaxKind::ElementList => Some(*tree),
+            _ => None,
+        });
+    list.map(|list| {
+        cst.children(list)
+            .iter()
+            .filter_map(|child| match child {
+                Child::Tree(tree) => Some((Element::cast(cst, *tree)?, cst.start(*tree))),
+                Child::Token(_) => None,
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Return the code of the class stored in the file, without the
+/// `within` clause
+fn class_code(path: &Path, code: &str) -> Result<String, String> {
+    let cst = parse(code, &path.display().to_string()).map_err(|e| e.to_string())?;
+    let tokens = cst.tokens();
+    let Some(root) = cst.root() else {
+        return Err(format!(
+            "{}: file must contain a single class",
+            path.display()
+        ));
+    };
+    let within = cst.children(root).iter().take_while(|c| match c {
+        Child::Tree(tree) => cst.kind(*tree) == SyntaxKind::Name,
+        Child::Token(tok) => tokens.kind(*tok) == TokenKind::Within,
+    });
+    // Skip the semicolon ending the `within` clause
+    let skipped = match within.count() {
+        0 => 0,
+        n => n + 1,
+    };
+    let (first, semicolon) = match &cst.children(root)[skipped..] {
+        [Child::Tree(class), Child::Token(semicolon)] => (cst.start(*class), *semicolon),
+        [Child::Token(prefix), Child::Tree(_), Child::Token(semicolon)] => (*prefix, *semicolon),
+        _ => {
+            return Err(format!(
+                "{}: file must contain a single class",
+                path.display()
+            ))
+        }
+    };
+    let start = tokens
+        .leading_trivia(first)
+        .iter()
+        .find_map(|t| match t {
+            Trivia::Token(comment) => Some(tokens.range(*comment).start),
+            Trivia::Whitespace(_) => None,
+        })
+        .unwrap_or(tokens.range(first).start);
+    let end = class_range(tokens, first, semicolon).end;
+    Ok(String::from(&code[start..end]))
+}
+
+/// Return the token before which nested classes are inserted: the
+/// first token following the public elements of the package
+fn insertion_point(cst: &ModelicaCST, package: ClassDefinition) -> TokenID {
+    if let Some(composition) = package.composition() {
+        let mut children = cst.children(composition.syntax()).iter().peekable();
+        if let Some(Child::Tree(tree)) = children.peek() {
+            if cst.kind(*tree) == SyntaxKind::ElementList {
+                children.next();
+            }
+        }
+        match children.next() {
+            Some(Child::Token(tok)) => return *tok,
+            Some(Child::Tree(tree)) => return cst.start(*tree),
+            None => (),
+        }
+    }
+    let specifier = cst
+        .descendants(package.syntax())
+        .find(|t| cst.kind(*t) == SyntaxKind::LongClassSpecifier)
+        .unwrap();
+    cst.children(specifier)
+        .iter()
+        .find_map(|c| match c {
+            Child::Token(tok) if cst.tokens().kind(*tok) == TokenKind::End => Some(*tok),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(code: &str) -> Result<Vec<(String, String)>, String> {
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            String::from(code),
+            EntryPoint::StoredDefinition,
+        );
+        Ok(cst
+            .split_package()?
+            .into_iter()
+            .map(|f| (f.path.to_string_lossy().replace('\\', "/"), f.code))
+            .collect())
+    }
+
+    #[test]
+    fn test_split_package() {
+        let code = "within Lib;\npackage P \"Foo\"\n  import SI = Modelica.Units.SI;\n\n  // Bar model\n  model Bar\n    Real x = 1;\n  end Bar; // bar\n\n  package Sub\n    function f\n      input Real x;\n      annotation (Documentation(info = \"<html>\n  foo\n</html>\"));\n    end f;\n  end Sub;\n\n  constant Real c = 1;\nprotected\n  model Hidden\n  end Hidden;\n  annotation (version = \"1.0\");\nend P;\n";
+        let files = split(code).unwrap();
+        let expected = [
+            (
+                "P/package.mo",
+                "within Lib;\npackage P \"Foo\"\n  import SI = Modelica.Units.SI;\n\n  constant Real c = 1;\nprotected\n  model Hidden\n  end Hidden;\n  annotation (version = \"1.0\");\nend P;\n",
+            ),
+            ("P/package.order", "Bar\nSub\nc\nHidden\n"),
+            (
+                "P/Bar.mo",
+                "within Lib.P;\n// Bar model\nmodel Bar\n  Real x = 1;\nend Bar; // bar\n",
+            ),
+            ("P/Sub/package.mo", "within Lib.P;\npackage Sub\nend Sub;\n"),
+            ("P/Sub/package.order", "f\n"),
+            (
+                "P/Sub/f.mo",
+                "within Lib.P.Sub;\nfunction f\n  input Real x;\n  annotation (Documentation(info = \"<html>\n  foo\n</html>\"));\nend f;\n",
+            ),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(p, c)| (String::from(*p), String::from(*c)))
+            .collect();
+        assert_eq!(files, expected);
+    }
+
+    fn merge(files: &[(&str, &str)]) -> Result<String, String> {
+        let files: Vec<PackageFile> = files
+            .iter()
+            .map(|(p, c)| PackageFile {
+                path: PathBuf::from(p),
+                code: String::from(*c),
+            })
+            .collect();
+        merge_package(&files, &FormatOptions::default())
+    }
+
+    #[test]
+    fn test_merge_package() {
+        let merged = merge(&[
+            (
+                "package.mo",
+                "within Lib;\npackage P\n  constant Real c = 1;\nprotected\n  model Hidden end Hidden;\nend P;\n",
+            ),
+            ("package.order", "c\nSub\nB\n"),
+            ("A.mo", "within Lib.P;\n// A\nmodel A end A;\n"),
+            ("B.mo", "within Lib.P;\nfunction B end B;"),
+            ("Sub/package.mo", "within Lib.P;\npackage Sub end Sub;"),
+            ("Sub/C.mo", "within Lib.P.Sub; final type C = Real; // C"),
+            ("README.md", "# Lib"),
+        ])
+        .unwrap();
+        let expected = "within Lib;\npackage P\n\n  constant Real c = 1;\n  package Sub\n\n    final type C = Real; // C\n\n  end Sub;\n  function B\n\n  end B;\n  // A\n  model A\n\n  end A;\n\nprotected\n\n  model Hidden\n\n  end Hidden;\n\nend P;\n";
+        assert_eq!(merged, expected);
+        assert!(merge(&[("A.mo", "model A end A;")]).is_err());
+        assert!(merge(&[("package.mo", "model A end A;")]).is_err());
+        assert!(merge(&[("package.mo", "package P end P;"), ("A.mo", "model A")]).is_err());
+        // Directory without package.mo
+        let error = merge(&[
+            ("package.mo", "package P end P;"),
+            ("Sub/A.mo", "within P.Sub; model A end A;"),
+        ])
+        .unwrap_err();
+        assert_eq!(error.replace('\\', "/"), "Sub/package.mo: file doesn't exist");
+    }
+
+    #[test]
+    fn test_merge_package_order() {
+        let merged = merge(&[
+            (
+                "package.mo",
+                "package P\n  // Constant\n  constant Real c = 1;\n  model M end M;\nend P;",
+            ),
+            ("package.order", "Sub\nc\nA\nM\nB\n"),
+            ("A.mo", "model A end A;"),
+            ("B.mo", "model B end B;"),
+            ("Sub/package.mo", "package Sub end Sub;"),
+        ])
+        .unwrap();
+        let expected = "package P\n\n  package Sub\n\n  end Sub;\n  // Constant\n  constant Real c = 1;\n  model A\n\n  end A;\n  model M\n\n  end M;\n  model B\n\n  end B;\n\nend P;\n";
+        assert_eq!(merged, expected);
+        // Elements defined in the same line
+        let merged = merge(&[
+            (
+                "package.mo",
+                "package P constant Real c = 1; constant Real d = 2; end P;",
+            ),
+            ("package.order", "c\nA\nd\n"),
+            ("A.mo", "model A end A;"),
+        ])
+        .unwrap();
+        let expected = "package P\n\n  constant Real c = 1;\n  model A\n\n  end A;\n  constant Real d = 2;\n\nend P;\n";
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let code = "within Lib;\npackage P\n  // A\n  model A\n    Real x;\n  end A;\n  constant Real c = 1;\n  package Sub\n    type T = Real;\n  end Sub;\n  function f\n  end f;\nend P;\n";
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            String::from(code),
+            EntryPoint::StoredDefinition,
+        );
+        let files: Vec<PackageFile> = cst
+            .split_package()
+            .unwrap()
+            .into_iter()
+            .map(|f| PackageFile {
+                path: f.path.strip_prefix("P").unwrap().to_path_buf(),
+                code: f.code,
+            })
+            .collect();
+        assert_eq!(
+            merge_package(&files, &FormatOptions::default()),
+            Ok(cst.pretty_print())
+        );
+    }
+
+    #[test]
+    fn test_check_package_order() {
+        let files: Vec<PackageFile> = [
+            (
+                "package.mo",
+                "package P\n  constant Real c = 1, d = 2;\n  parameter Real p = 1;\n  model M end M;\nend P;",
+            ),
+            ("package.order", "B\nX\nc\nB\n\nM\n"),
+            ("A.mo", "model A end A;"),
+            ("B.mo", "model B end B;"),
+            ("Sub/package.mo", "package Sub end Sub;"),
+            ("Sub/package.order", "Y\n"),
+            ("Sub/C.mo", "model C end C;"),
+        ]
+        .iter()
+        .map(|(p, c)| PackageFile {
+            path: PathBuf::from(p),
+            code: String::from(*c),
+        })
+        .collect();
+        let order = check_package_order(&files).unwrap();
+        assert_eq!(order.missing, vec!["d", "A", "Sub"]);
+        assert_eq!(order.extra, vec!["X", "B"]);
+        assert_eq!(order.code, "B\nc\nM\nd\nA\nSub\n");
+        // Regenerated file is valid
+        let mut files = files;
+        files[1].code = order.code.clone();
+        let order = check_package_order(&files).unwrap();
+        assert!(order.missing.is_empty() && order.extra.is_empty());
+        assert_eq!(order.code, files[1].code);
+        // Missing package.mo
+        assert!(check_package_order(&files[1..]).is_err());
+    }
+
+    #[test]
+    fn test_split_and_check_order() {
+        let code = "package P\n  constant Real c = 1;\n  model A\n  end A;\n  package Sub\n    model B\n    end B;\n  end Sub;\nprotected\n  model Hidden\n  end Hidden;\nend P;\n";
+        let cst = ModelicaCST::from(
+            String::from("test"),
+            String::from(code),
+            EntryPoint::StoredDefinition,
+        );
+        let files = cst.split_package().unwrap();
+        for dir in ["P", "P/Sub"] {
+            let files: Vec<PackageFile> = files
+                .iter()
+                .filter_map(|f| {
+                    let path = f.path.strip_prefix(dir).ok()?;
+                    Some(PackageFile {
+                        path: path.to_path_buf(),
+                        code: f.code.clone(),
+                    })
+                })
+                .collect();
+            let order = check_package_order(&files).unwrap();
+            assert!(order.missing.is_empty() && order.extra.is_empty());
+            assert_eq!(Some(order.code.as_str()), find_file(&files, Path::new("package.order")));
+        }
+    }
+
+    #[test]
+    fn test_split_errors() {
+        assert!(split("model M end M;").is_err());
+        assert!(split("package P end P; package Q end Q;").is_err());
+        assert!(split("package P = Q;").is_err());
+        assert_eq!(split("package P end P;").unwrap().len(), 1);
+        // Package without nested classes
+        let files = split("package P\n  constant Real c = 1;\nend P;").unwrap();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    String::from("P/package.mo"),
+                    String::from("package P\n  constant Real c = 1;\nend P;")
+                ),
+                (String::from("P/package.order"), String::from("c\n"))
+            ]
+        );
+    }
+}
@@ -2,12 +2,54 @@ mod lexing;
 mod parsing;
 mod tokens;
 mod cst;
+mod visitor;
+mod incremental;
+mod gap;
+mod errors;
+pub mod ast;
 
 // Re-exports
 
-pub use tokens::{TokenKind, TokenID, Position, Tokens};
-pub use parsing::SyntaxKind;
-pub use cst::{Child, ModelicaCST, TreeID};
+pub use tokens::{ColumnUnit, TokenKind, TokenID, Position, Tokens, Trivia};
+pub use parsing::{EntryPoint, SyntaxKind};
+pub use cst::{Ancestors, Child, Descendants, ModelicaCST, TokensIn, TreeID};
+pub use visitor::Visitor;
+pub use errors::ParseErrors;
+
+/// Parse Modelica source code into a concrete syntax tree (CST).
+///
+/// # Arguments
+/// * `source` - The Modelica code to parse.
+/// * `name` - The source file name used in error messages.
+///
+/// # Returns
+/// A `ModelicaCST` object, or `ParseErrors` if the code contains
+/// lexical or syntax errors.
+pub fn parse(source: &str, name: &str) -> Result<ModelicaCST, ParseErrors> {
+    parse_entry(source, name, EntryPoint::StoredDefinition)
+}
+
+/// Parse Modelica script (`.mos`) into a concrete syntax tree (CST).
+///
+/// Scripts are sequences of statements, expressions, imports and class
+/// definitions separated with semicolons. Arguments and returned value
+/// are the same as in `parse`.
+pub fn parse_script(source: &str, name: &str) -> Result<ModelicaCST, ParseErrors> {
+    parse_entry(source, name, EntryPoint::Script)
+}
+
+/// Parse the code starting from the specified entry point and collect
+/// lexical and syntax errors
+fn parse_entry(source: &str, name: &str, entry: EntryPoint) -> Result<ModelicaCST, ParseErrors> {
+    let cst = ModelicaCST::from(String::from(name), String::from(source), entry);
+    let mut errors = cst.tokens().errors();
+    errors.append(&mut cst.errors());
+    if errors.is_empty() {
+        Ok(cst)
+    } else {
+        Err(ParseErrors::new(errors))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -17,7 +59,7 @@ mod tests {
     #[test]
     fn test_parse_correct_modelica_code() {
         let code = "model Complex \n parameter Real x = 1.0;  \n end Complex;".to_string();
-        let cst = ModelicaCST::from(String::from("test"), code, SyntaxKind::StoredDefinition);
+        let cst = ModelicaCST::from(String::from("test"), code, EntryPoint::StoredDefinition);
 
         // Check token kinds
         let tokens = cst.tokens();
@@ -43,10 +85,346 @@ mod tests {
         assert_eq!(cst.children(root).len(), 2);
     }
 
+    #[test]
+    fn test_trivia() {
+        let code = "// header\nmodel Foo /* a */ // b\r\n  Real x;\n\n  // c\nend Foo; \n// trailer\n".to_string();
+        let cst = ModelicaCST::from(String::from("test"), code.clone(), EntryPoint::StoredDefinition);
+        let tokens = cst.tokens();
+        let text = |trivia: Vec<Trivia>| -> Vec<String> {
+            trivia.iter().map(|t| tokens.trivia_text(t).to_string()).collect()
+        };
+
+        // Check trivia attached to tokens
+        let significant = tokens.tokens();
+        assert_eq!(text(tokens.leading_trivia(significant[0])), vec!["// header", "\n"]);
+        assert_eq!(text(tokens.trailing_trivia(significant[0])), vec![" "]);
+        assert_eq!(
+            text(tokens.trailing_trivia(significant[1])),
+            vec![" ", "/* a */", " ", "// b", "\r\n"]
+        );
+        assert_eq!(text(tokens.leading_trivia(significant[2])), vec!["  "]);
+        assert_eq!(text(tokens.leading_trivia(significant[5])), vec!["\n  ", "// c", "\n"]);
+        assert_eq!(
+            text(tokens.trailing_trivia(*significant.last().unwrap())),
+            vec![" \n", "// trailer", "\n"]
+        );
+
+        // Check source reconstruction
+        assert_eq!(cst.to_source(), code);
+        let sample = include_str!("../tests/samples/code-input.mo").to_string();
+        let cst = ModelicaCST::from(String::from("test"), sample.clone(), EntryPoint::StoredDefinition);
+        assert_eq!(cst.to_source(), sample);
+    }
+
+    #[test]
+    fn test_node_ranges() {
+        let code = "model Foo\n  Real x = 1 + y;\nend Foo;".to_string();
+        let cst = ModelicaCST::from(String::from("test"), code, EntryPoint::StoredDefinition);
+        let root = cst.root().unwrap();
+        assert_eq!(cst.text_range(root), 0..36);
+        assert_eq!(cst.text(root), cst.tokens().code());
+
+        // Check lookups
+        let node = cst.node_at_offset(21).unwrap();
+        assert_eq!(cst.kind(node), SyntaxKind::Primary);
+        assert_eq!(cst.text(node), "1");
+        let node = cst.node_at_offset(25).unwrap();
+        assert_eq!(cst.kind(node), SyntaxKind::ComponentReference);
+        assert_eq!(cst.text(node), "y");
+        let node = cst.covering_node(21..26).unwrap();
+        assert_eq!(cst.kind(node), SyntaxKind::ArithmeticExpression);
+        assert_eq!(cst.text(node), "1 + y");
+        let node = cst.covering_node(12..16).unwrap();
+        assert_eq!(cst.kind(node), SyntaxKind::Name);
+        let node = cst.covering_node(6..20).unwrap();
+        assert_eq!(cst.kind(node), SyntaxKind::LongClassSpecifier);
+        assert_eq!(cst.node_at_offset(40), None);
+
+        // Check positions
+        let pos = cst.tokens().position(14).unwrap();
+        assert_eq!((pos.line, pos.col), (2, 5));
+        assert!(cst.tokens().position(37).is_none());
+        assert!(cst.is_multiline(root));
+        assert!(!cst.is_multiline(cst.covering_node(12..26).unwrap()));
+    }
+
+    #[test]
+    fn test_tree_walk() {
+        let code = "model Foo\n  Real x = 1 + y;\nend Foo;".to_string();
+        let cst = ModelicaCST::from(String::from("test"), code, EntryPoint::StoredDefinition);
+        let root = cst.root().unwrap();
+
+        // Check iterators
+        let refs: Vec<TreeID> = cst
+            .descendants(root)
+            .filter(|t| cst.kind(*t) == SyntaxKind::Primary)
+            .collect();
+        assert_eq!(refs.len(), 2);
+        let ancestors: Vec<SyntaxKind> = cst.ancestors(refs[1]).map(|t| cst.kind(t)).collect();
+        assert_eq!(ancestors.first(), Some(&SyntaxKind::Primary));
+        assert_eq!(ancestors.last(), Some(&SyntaxKind::StoredDefinition));
+        let term = cst.parent(cst.parent(refs[1]).unwrap()).unwrap();
+        let siblings: Vec<SyntaxKind> = cst.siblings(term).map(|t| cst.kind(t)).collect();
+        assert_eq!(siblings, vec![SyntaxKind::Term, SyntaxKind::AddOperator]);
+        let tokens: Vec<&str> = cst
+            .tokens_in(refs[1])
+            .map(|t| cst.tokens().text(t))
+            .collect();
+        assert_eq!(tokens, vec!["y"]);
+        assert_eq!(cst.tokens_in(root).count(), cst.tokens().tokens().len());
+
+        // Check visitor
+        struct Depth {
+            current: usize,
+            max: usize,
+            tokens: usize,
+        }
+        impl Visitor for Depth {
+            fn enter(&mut self, _cst: &ModelicaCST, kind: SyntaxKind, _id: TreeID) -> bool {
+                self.current += 1;
+                self.max = self.max.max(self.current);
+                kind != SyntaxKind::Composition
+            }
+            fn exit(&mut self, _cst: &ModelicaCST, _kind: SyntaxKind, _id: TreeID) {
+                self.current -= 1;
+            }
+            fn token(&mut self, _cst: &ModelicaCST, _kind: TokenKind, _id: TokenID) {
+                self.tokens += 1;
+            }
+        }
+        let mut visitor = Depth {
+            current: 0,
+            max: 0,
+            tokens: 0,
+        };
+        cst.walk(root, &mut visitor);
+        assert_eq!(visitor.current, 0);
+        assert_eq!(visitor.max, 5);
+        assert_eq!(visitor.tokens, 5);
+    }
+
+    #[test]
+    fn test_parse_result() {
+        let cst = parse("model Foo\n  Real x;\nend Foo;", "foo.mo").unwrap();
+        assert_eq!(cst.tokens().source(), "foo.mo");
+        assert_eq!(cst.kind(cst.root().unwrap()), SyntaxKind::StoredDefinition);
+
+        // Lexical and syntax errors are merged
+        let errors = match parse("model Foo\n  Real x = $;\n  Real y\nend Foo;", "foo.mo") {
+            Ok(_) => panic!("errors not detected"),
+            Err(errors) => errors,
+        };
+        assert_eq!(errors.messages().len(), 3);
+        assert!(errors.messages()[0].starts_with("foo.mo:2:12: illegal character"));
+        assert!(errors.to_string().starts_with(&errors.messages()[0]));
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let code = "import Modelica.Constants;\nloadFile(\"foo.mo\");\nx := 1;\n(a, b) := f(x);\nx + 1;\nfor i in 1:2 loop\n  y := i;\nend for;\nmodel Foo end Foo;\npure(x);\n";
+        let cst = parse_script(code, "foo.mos").unwrap();
+        let root = cst.root().unwrap();
+        assert_eq!(cst.kind(root), SyntaxKind::Script);
+        let kinds: Vec<SyntaxKind> = cst
+            .children(root)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Tree(t) => Some(cst.kind(*t)),
+                Child::Token(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                SyntaxKind::ImportClause,
+                SyntaxKind::Expression,
+                SyntaxKind::Statement,
+                SyntaxKind::Statement,
+                SyntaxKind::Expression,
+                SyntaxKind::Statement,
+                SyntaxKind::ClassDefinition,
+                SyntaxKind::Expression,
+            ]
+        );
+        assert!(parse("x := 1;", "foo.mo").is_err());
+        assert!(parse_script("x := 1", "foo.mos").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_script() {
+        let cases = [
+            ")",
+            "]",
+            ", ;",
+            "else",
+            "protected",
+            "x = 1;",
+            "x := 1 )",
+            "within A;",
+            "equation x = 1;",
+            "annotation();",
+            "end for;",
+        ];
+        for code in cases {
+            assert!(parse_script(code, "foo.mos").is_err(), "{code}");
+        }
+        let errors = parse_script(") x := 1;", "foo.mos").err().unwrap();
+        assert_eq!(
+            errors.to_string(),
+            "foo.mos:1:1: unexpected token ')' in the script"
+        );
+    }
+
+    #[test]
+    fn test_extension_keywords() {
+        let code = "Real optimization, constraint, uniontype, match, local;";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::Element);
+        let keywords = !cfg!(any(feature = "optimica", feature = "metamodelica"));
+        assert_eq!(cst.errors().is_empty(), keywords);
+        #[cfg(feature = "metamodelica")]
+        {
+            let code = "list<tuple<Integer, Real>>";
+            let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::TypeSpecifier);
+            assert!(cst.errors().is_empty());
+            assert_eq!(cst.tokens().tokens().len(), 9);
+        }
+        #[cfg(feature = "optimica")]
+        {
+            let code = "optimization Foo(objective = x) constraint x >= 0; end Foo;";
+            let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::StoredDefinition);
+            assert!(cst.errors().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_lexical_errors() {
+        let code = "x = {1e, 1.E+, 2.5e-3, 1.};\ny = \"a\\n\\q\\\"\";\nz = 'q";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::ElementList);
+        let kinds: Vec<TokenKind> = cst.tokens().all().into_iter().map(|t| cst.tokens().kind(t)).collect();
+        assert_eq!(kinds[3], TokenKind::ErrorInvalidNumber);
+        assert_eq!(kinds[5], TokenKind::ErrorInvalidNumber);
+        assert_eq!(kinds[7], TokenKind::UReal);
+        assert_eq!(kinds[9], TokenKind::UReal);
+        assert_eq!(kinds[14], TokenKind::ErrorInvalidEscape);
+        assert_eq!(
+            cst.tokens().errors(),
+            [
+                "test:1:7: missing digits in the exponent of numeric literal '1e'",
+                "test:1:12: missing digits in the exponent of numeric literal '1.E+'",
+                "test:2:9: invalid escape sequence '\\q'",
+                "test:3:5: unclosed quoted identifier",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_quoted_identifier() {
+        let code = "model A\n  Real 'x";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::StoredDefinition);
+        let tokens = cst.tokens();
+        let kinds: Vec<TokenKind> = tokens.all().into_iter().map(|t| tokens.kind(t)).collect();
+        assert_eq!(kinds[3], TokenKind::ErrorUnclosedQIdent);
+        assert_eq!(tokens.errors(), ["test:2:8: unclosed quoted identifier"]);
+    }
+
+    #[test]
+    fn test_unicode() {
+        let code = "model Żółw \"Zażółć gęślą jaźń\" // Straße\n  Real 'x' \"温度 in °C\" /* 注释 */;\nend Żółw;";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::StoredDefinition);
+        assert_eq!(cst.to_source(), code);
+        let tokens = cst.tokens();
+        let kinds: Vec<TokenKind> = tokens.all().into_iter().map(|t| tokens.kind(t)).collect();
+        assert_eq!(kinds[1], TokenKind::ErrorIllegalCharacter);
+        assert_eq!(kinds[4], TokenKind::Identifier);
+        assert_eq!(kinds[5], TokenKind::String);
+        assert_eq!(kinds[6], TokenKind::LineComment);
+        assert_eq!(kinds[9], TokenKind::String);
+        assert_eq!(kinds[10], TokenKind::BlockComment);
+        let string = tokens.get(tokens.all()[9]);
+        assert_eq!(string.text, "\"温度 in °C\"");
+        assert_eq!((string.start.line, string.start.col), (2, 12));
+        assert_eq!((string.end.line, string.end.col), (2, 22));
+        assert_eq!(
+            tokens.errors()[..3],
+            [
+                "test:1:7: illegal character 'Ż'",
+                "test:1:8: illegal character 'ó'",
+                "test:1:9: illegal character 'ł'"
+            ]
+        );
+
+        // Polish, German and CJK description strings are kept intact
+        let code = "model Foo \"Zażółć gęślą jaźń\"\n  Real x \"Größe\";\n  Real y \"温度\";\nend Foo;";
+        let cst = parse(code, "test").unwrap();
+        assert_eq!(
+            cst.pretty_print(),
+            "model Foo\n  \"Zażółć gęślą jaźń\"\n\n  Real x\n    \"Größe\";\n  Real y\n    \"温度\";\n\nend Foo;\n"
+        );
+
+        let cst = ModelicaCST::from(String::from("test"), String::from("Real 'ab€' = 1;"), EntryPoint::Element);
+        assert_eq!(
+            cst.tokens().errors()[0],
+            "test:1:9: illegal character inside quoted identifier '€'"
+        );
+    }
+
+    #[test]
+    fn test_column_units() {
+        let code = "x = \"ż😀\";\r\ny = 1;";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::ElementList);
+        let tokens = cst.tokens();
+        // Closing quote of the string
+        let offset = code.find("\";").unwrap();
+        assert_eq!(tokens.line(offset), 1);
+        assert_eq!(tokens.column(offset, ColumnUnit::Byte), Some(12));
+        assert_eq!(tokens.column(offset, ColumnUnit::Char), Some(8));
+        assert_eq!(tokens.column(offset, ColumnUnit::Utf16), Some(9));
+        assert_eq!(tokens.position(offset).map(|p| p.col), Some(8));
+        for unit in [ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16] {
+            let col = tokens.column(offset, unit).unwrap();
+            assert_eq!(tokens.offset(1, col, unit), Some(offset));
+        }
+        // Columns inside characters
+        assert_eq!(tokens.offset(1, 7, ColumnUnit::Byte), None);
+        assert_eq!(tokens.offset(1, 8, ColumnUnit::Utf16), None);
+        // Line ends and lines out of range
+        assert_eq!(tokens.offset(1, 10, ColumnUnit::Char), Some(code.find('\r').unwrap()));
+        assert_eq!(tokens.offset(1, 11, ColumnUnit::Char), None);
+        assert_eq!(tokens.offset(2, 1, ColumnUnit::Utf16), Some(code.find('y').unwrap()));
+        assert_eq!(tokens.offset(2, 7, ColumnUnit::Byte), Some(code.len()));
+        assert_eq!(tokens.offset(3, 1, ColumnUnit::Byte), None);
+        assert_eq!(tokens.offset(0, 1, ColumnUnit::Byte), None);
+    }
+
+    #[test]
+    fn test_offsets_inside_characters() {
+        let code = "x = \"ż😀\";";
+        let cst = ModelicaCST::from(String::from("test"), String::from(code), EntryPoint::ElementList);
+        let tokens = cst.tokens();
+        let emoji = code.find('😀').unwrap();
+        assert_eq!(tokens.position(emoji).map(|p| p.col), Some(7));
+        assert_eq!(tokens.column(emoji, ColumnUnit::Utf16), Some(7));
+        // Offsets inside characters and past the end of the code
+        for offset in [emoji - 1, emoji + 1, emoji + 3, code.len() + 1] {
+            assert!(tokens.position(offset).is_none());
+            assert!(tokens.column(offset, ColumnUnit::Byte).is_none());
+            assert!(cst.node_at_offset(offset).is_none());
+        }
+        assert!(cst.node_at_offset(code.len()).is_none());
+        assert!(cst.node_at_offset(usize::MAX).is_none());
+        // Offset of the multibyte character is found in the string literal
+        let node = cst.node_at_offset(emoji).unwrap();
+        assert_eq!(cst.text(node), "\"ż😀\"");
+        assert!(cst.covering_node(emoji..emoji + 2).is_none());
+        assert!(cst.covering_node(emoji + 4..emoji).is_none());
+        assert!(cst.covering_node(0..code.len() + 1).is_none());
+        assert!(cst.covering_node(emoji..emoji + 4).is_some());
+    }
+
     #[test]
     fn test_parse_incorrect_modelica_code() {
         let code = "model Example end Example".to_string();
-        let cst = ModelicaCST::from(String::from("test"), code, SyntaxKind::StoredDefinition);
+        let cst = ModelicaCST::from(String::from("test"), code, EntryPoint::StoredDefinition);
 
         // Check syntax errors
         let errors = cst.errors();
@@ -0,0 +1,791 @@
+//! Typed API over the concrete syntax tree.
+//!
+//! Each node type wraps a reference to the `ModelicaCST` and the
+//! `TreeID` of the node of the corresponding `SyntaxKind`. Accessors
+//! return optional values, as the tree may be incomplete in case of
+//! syntax errors.
+//!
+//! # Example
+//!
+//! ```
+//! use mofmt::ast::{AstNode, StoredDefinition};
+//! use mofmt::{EntryPoint, ModelicaCST};
+//!
+//! let code = String::from("model Foo \"Bar\" Real x; end Foo;");
+//! let cst = ModelicaCST::from(String::from("foo.mo"), code, EntryPoint::StoredDefinition);
+//! let root = StoredDefinition::cast(&cst, cst.root().unwrap()).unwrap();
+//! let class = root.class_definitions()[0];
+//! assert_eq!(class.name(), Some("Foo"));
+//! assert_eq!(class.description().unwrap().text(), "\"Bar\"");
+//! ```
+use super::cst::{Child, ModelicaCST, TreeID};
+use super::parsing::SyntaxKind;
+use super::tokens::{TokenID, TokenKind};
+
+/// Common interface of all typed nodes.
+pub trait AstNode<'a>: Sized + Copy {
+    /// Return the typed node if the node of the given id is of the
+    /// matching kind.
+    fn cast(cst: &'a ModelicaCST, id: TreeID) -> Option<Self>;
+
+    /// Return the id of the wrapped node.
+    fn syntax(&self) -> TreeID;
+
+    /// Return the CST that contains the node.
+    fn cst(&self) -> &'a ModelicaCST;
+
+    /// Return the source code of the node
+    fn text(&self) -> &'a str {
+        let cst = self.cst();
+        let start = cst.tokens().range(cst.start(self.syntax())).start;
+        let end = cst.tokens().range(cst.end(self.syntax())).end;
+        &cst.tokens().code()[start..end]
+    }
+}
+
+macro_rules! ast_nodes {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[derive(Clone, Copy)]
+            #[doc = concat!("Typed `", stringify!($name), "` node.")]
+            pub struct $name<'a> {
+                cst: &'a ModelicaCST,
+                id: TreeID,
+            }
+
+            impl<'a> AstNode<'a> for $name<'a> {
+                fn cast(cst: &'a ModelicaCST, id: TreeID) -> Option<Self> {
+                    if cst.kind(id) == SyntaxKind::$name {
+                        Some($name { cst, id })
+                    } else {
+                        None
+                    }
+                }
+
+                fn syntax(&self) -> TreeID {
+                    self.id
+                }
+
+                fn cst(&self) -> &'a ModelicaCST {
+                    self.cst
+                }
+            }
+        )*
+    };
+}
+
+ast_nodes!(
+    StoredDefinition,
+    ClassDefinition,
+    ClassPrefixes,
+    Composition,
+    Element,
+    ImportClause,
+    ExtendsClause,
+    ComponentClause,
+    TypePrefix,
+    ComponentDeclaration,
+    Declaration,
+    Modification,
+    ClassModification,
+    ElementModification,
+    EquationSection,
+    AlgorithmSection,
+    Equation,
+    Statement,
+    Expression,
+    TypeSpecifier,
+    Name,
+    ComponentReference,
+    ArraySubscripts,
+    Description,
+    DescriptionString,
+    AnnotationClause,
+);
+
+impl<'a> StoredDefinition<'a> {
+    /// Return the name from the `within` clause
+    pub fn within(&self) -> Option<Name<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return all class definitions stored in the file
+    pub fn class_definitions(&self) -> Vec<ClassDefinition<'a>> {
+        children(self.cst, self.id)
+    }
+}
+
+impl<'a> ClassDefinition<'a> {
+    /// Return the name of the class
+    pub fn name(&self) -> Option<&'a str> {
+        let specifier = self.specifier()?;
+        let name = token(self.cst, specifier, TokenKind::Identifier)?;
+        Some(self.cst.tokens().text(name))
+    }
+
+    /// Return the name following the `end` keyword
+    pub fn end_name(&self) -> Option<&'a str> {
+        let specifier = self.specifier()?;
+        if self.cst.kind(specifier) != SyntaxKind::LongClassSpecifier {
+            return None;
+        }
+        match self.cst.children(specifier).last()? {
+            Child::Token(tok) if self.cst.tokens().kind(*tok) == TokenKind::Identifier => {
+                Some(self.cst.tokens().text(*tok))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return `true` if the class is encapsulated
+    pub fn is_encapsulated(&self) -> bool {
+        token(self.cst, self.id, TokenKind::Encapsulated).is_some()
+    }
+
+    /// Return `true` if the class extends the inherited class of the
+    /// same name (`class extends Foo`)
+    pub fn is_extends(&self) -> bool {
+        self.specifier()
+            .and_then(|s| token(self.cst, s, TokenKind::Extends))
+            .is_some()
+    }
+
+    /// Return class prefixes
+    pub fn prefixes(&self) -> Option<ClassPrefixes<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the modification of the short class definition or of the
+    /// extended class
+    pub fn modification(&self) -> Option<ClassModification<'a>> {
+        child(self.cst, self.specifier()?)
+    }
+
+    /// Return the composition of the long class definition
+    pub fn composition(&self) -> Option<Composition<'a>> {
+        child(self.cst, self.specifier()?)
+    }
+
+    /// Return the description string of the class
+    pub fn description(&self) -> Option<DescriptionString<'a>> {
+        let specifier = self.specifier()?;
+        child(self.cst, specifier).or_else(|| child::<Description>(self.cst, specifier)?.string())
+    }
+
+    /// Return the class annotation
+    pub fn annotation(&self) -> Option<AnnotationClause<'a>> {
+        let specifier = self.specifier()?;
+        match self.composition() {
+            Some(composition) => composition.annotation(),
+            None => child::<Description>(self.cst, specifier)?.annotation(),
+        }
+    }
+
+    /// Return the long, short or derivative class specifier
+    fn specifier(&self) -> Option<TreeID> {
+        let specifier = child_of_kind(self.cst, self.id, SyntaxKind::ClassSpecifier)?;
+        match self.cst.children(specifier).first()? {
+            Child::Tree(tree) => Some(*tree),
+            Child::Token(_) => None,
+        }
+    }
+}
+
+impl<'a> ClassPrefixes<'a> {
+    /// Return `true` if the class is partial
+    pub fn is_partial(&self) -> bool {
+        token(self.cst, self.id, TokenKind::Partial).is_some()
+    }
+
+    /// Return the kind of the keyword that determines the class
+    /// restriction, e.g. `TokenKind::Model` or `TokenKind::Function`
+    pub fn restriction(&self) -> Option<TokenKind> {
+        match self.cst.children(self.id).last()? {
+            Child::Token(tok) => Some(self.cst.tokens().kind(*tok)),
+            Child::Tree(_) => None,
+        }
+    }
+}
+
+impl<'a> Composition<'a> {
+    /// Return all elements of the class, both public and protected
+    pub fn elements(&self) -> Vec<Element<'a>> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Tree(t) if self.cst.kind(*t) == SyntaxKind::ElementList => Some(*t),
+                _ => None,
+            })
+            .flat_map(|list| children(self.cst, list))
+            .collect()
+    }
+
+    /// Return all equation sections
+    pub fn equation_sections(&self) -> Vec<EquationSection<'a>> {
+        children(self.cst, self.id)
+    }
+
+    /// Return all algorithm sections
+    pub fn algorithm_sections(&self) -> Vec<AlgorithmSection<'a>> {
+        children(self.cst, self.id)
+    }
+
+    /// Return the class annotation
+    pub fn annotation(&self) -> Option<AnnotationClause<'a>> {
+        // Annotation of the external function is placed before the
+        // semicolon that ends the external clause
+        let mut in_external = false;
+        let mut annotation = None;
+        for child in self.cst.children(self.id) {
+            match child {
+                Child::Token(tok) => match self.cst.tokens().kind(*tok) {
+                    TokenKind::External => in_external = true,
+                    TokenKind::Semicolon => in_external = false,
+                    _ => (),
+                },
+                Child::Tree(tree) => {
+                    if !in_external {
+                        annotation = AnnotationClause::cast(self.cst, *tree).or(annotation);
+                    }
+                }
+            }
+        }
+        annotation
+    }
+}
+
+impl<'a> Element<'a> {
+    /// Return kinds of element prefixes, like `redeclare`, `final` or
+    /// `replaceable`
+    pub fn prefixes(&self) -> Vec<TokenKind> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Token(tok) => Some(self.cst.tokens().kind(*tok)),
+                Child::Tree(_) => None,
+            })
+            .collect()
+    }
+
+    /// Return the import clause
+    pub fn import_clause(&self) -> Option<ImportClause<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the extends clause
+    pub fn extends_clause(&self) -> Option<ExtendsClause<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the class definition
+    pub fn class_definition(&self) -> Option<ClassDefinition<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the component clause
+    pub fn component_clause(&self) -> Option<ComponentClause<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the type specifier from the constraining clause
+    pub fn constraining_type(&self) -> Option<TypeSpecifier<'a>> {
+        let clause = child_of_kind(self.cst, self.id, SyntaxKind::ConstrainingClause)?;
+        child(self.cst, clause)
+    }
+
+    /// Return the description of the replaceable element
+    pub fn description(&self) -> Option<Description<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> ImportClause<'a> {
+    /// Return the imported name
+    pub fn name(&self) -> Option<Name<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the alias from `import Alias = Foo.Bar`
+    pub fn alias(&self) -> Option<&'a str> {
+        token(self.cst, self.id, TokenKind::Identifier).map(|t| self.cst.tokens().text(t))
+    }
+
+    /// Return names listed in `import Foo.{Bar, Baz}`
+    pub fn imported_names(&self) -> Vec<&'a str> {
+        child_of_kind(self.cst, self.id, SyntaxKind::ImportList)
+            .map(|list| {
+                self.cst
+                    .children(list)
+                    .iter()
+                    .filter_map(|c| match c {
+                        Child::Token(tok)
+                            if self.cst.tokens().kind(*tok) == TokenKind::Identifier =>
+                        {
+                            Some(self.cst.tokens().text(*tok))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Return `true` if the clause imports all elements (`import Foo.*`)
+    pub fn is_wildcard(&self) -> bool {
+        token(self.cst, self.id, TokenKind::DotStar).is_some()
+    }
+
+    /// Return the description of the clause
+    pub fn description(&self) -> Option<Description<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> ExtendsClause<'a> {
+    /// Return the type specifier of the extended class
+    pub fn type_specifier(&self) -> Option<TypeSpecifier<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the annotation of the clause
+    pub fn annotation(&self) -> Option<AnnotationClause<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> ComponentClause<'a> {
+    /// Return type prefixes, like `parameter` or `input`
+    pub fn prefixes(&self) -> Option<TypePrefix<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the type specifier of declared components
+    pub fn type_specifier(&self) -> Option<TypeSpecifier<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return array subscripts following the type specifier
+    pub fn subscripts(&self) -> Option<ArraySubscripts<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return all components declared in the clause
+    pub fn declarations(&self) -> Vec<ComponentDeclaration<'a>> {
+        child_of_kind(self.cst, self.id, SyntaxKind::ComponentList)
+            .map(|list| children(self.cst, list))
+            .unwrap_or_default()
+    }
+}
+
+impl<'a> TypePrefix<'a> {
+    /// Return kinds of all prefixes
+    pub fn kinds(&self) -> Vec<TokenKind> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Token(tok) => Some(self.cst.tokens().kind(*tok)),
+                Child::Tree(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> ComponentDeclaration<'a> {
+    /// Return the name of the component
+    pub fn name(&self) -> Option<&'a str> {
+        self.declaration()?.name()
+    }
+
+    /// Return the declaration
+    pub fn declaration(&self) -> Option<Declaration<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the modification of the component
+    pub fn modification(&self) -> Option<Modification<'a>> {
+        self.declaration()?.modification()
+    }
+
+    /// Return the expression from the condition attribute
+    pub fn condition(&self) -> Option<Expression<'a>> {
+        let condition = child_of_kind(self.cst, self.id, SyntaxKind::ConditionAttribute)?;
+        child(self.cst, condition)
+    }
+
+    /// Return the description of the component
+    pub fn description(&self) -> Option<Description<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> Declaration<'a> {
+    /// Return the declared name
+    pub fn name(&self) -> Option<&'a str> {
+        token(self.cst, self.id, TokenKind::Identifier).map(|t| self.cst.tokens().text(t))
+    }
+
+    /// Return array subscripts following the name
+    pub fn subscripts(&self) -> Option<ArraySubscripts<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the modification
+    pub fn modification(&self) -> Option<Modification<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> Modification<'a> {
+    /// Return the class modification
+    pub fn class_modification(&self) -> Option<ClassModification<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the binding expression
+    pub fn expression(&self) -> Option<Expression<'a>> {
+        let expr = child_of_kind(self.cst, self.id, SyntaxKind::ModificationExpression)?;
+        child(self.cst, expr)
+    }
+}
+
+impl<'a> ClassModification<'a> {
+    /// Return all element modifications listed in the class
+    /// modification. Element redeclarations and replaceables are
+    /// skipped.
+    pub fn element_modifications(&self) -> Vec<ElementModification<'a>> {
+        let mut modifications = Vec::new();
+        if let Some(list) = child_of_kind(self.cst, self.id, SyntaxKind::ArgumentList) {
+            for argument in self.cst.children(list) {
+                if let Child::Tree(argument) = argument {
+                    if let Some(m) = child_of_kind(
+                        self.cst,
+                        *argument,
+                        SyntaxKind::ElementModificationOrReplaceable,
+                    ) {
+                        modifications.extend(child::<ElementModification>(self.cst, m));
+                    }
+                }
+            }
+        }
+        modifications
+    }
+
+    /// Return the element modification of the given name
+    pub fn find(&self, name: &str) -> Option<ElementModification<'a>> {
+        self.element_modifications()
+            .into_iter()
+            .find(|m| m.name().map(|n| n.text()) == Some(name))
+    }
+}
+
+impl<'a> ElementModification<'a> {
+    /// Return the name of the modified element
+    pub fn name(&self) -> Option<Name<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the modification
+    pub fn modification(&self) -> Option<Modification<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the description string
+    pub fn description(&self) -> Option<DescriptionString<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> EquationSection<'a> {
+    /// Return `true` if the section is an initial section
+    pub fn is_initial(&self) -> bool {
+        token(self.cst, self.id, TokenKind::Initial).is_some()
+    }
+
+    /// Return all equations from the section
+    pub fn equations(&self) -> Vec<Equation<'a>> {
+        children(self.cst, self.id)
+    }
+}
+
+impl<'a> AlgorithmSection<'a> {
+    /// Return `true` if the section is an initial section
+    pub fn is_initial(&self) -> bool {
+        token(self.cst, self.id, TokenKind::Initial).is_some()
+    }
+
+    /// Return all statements from the section
+    pub fn statements(&self) -> Vec<Statement<'a>> {
+        children(self.cst, self.id)
+    }
+}
+
+impl<'a> Equation<'a> {
+    /// Return the kind of the equation. It is either a
+    /// `SyntaxKind::SimpleExpression` for simple equations and function
+    /// calls, or a kind of the compound equation, e.g.
+    /// `SyntaxKind::IfEquation`.
+    pub fn kind(&self) -> Option<SyntaxKind> {
+        match self.cst.children(self.id).first()? {
+            Child::Tree(tree) => Some(self.cst.kind(*tree)),
+            Child::Token(_) => None,
+        }
+    }
+
+    /// Return the right-hand side of the simple equation
+    pub fn rhs(&self) -> Option<Expression<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the description of the equation
+    pub fn description(&self) -> Option<Description<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> Statement<'a> {
+    /// Return the kind of the statement. It is either a
+    /// `SyntaxKind::ComponentReference` for assignments and function
+    /// calls, or a kind of the compound statement, e.g.
+    /// `SyntaxKind::ForStatement`.
+    pub fn kind(&self) -> Option<SyntaxKind> {
+        self.cst.children(self.id).iter().find_map(|c| match c {
+            Child::Tree(tree) => Some(self.cst.kind(*tree)),
+            Child::Token(_) => None,
+        })
+    }
+
+    /// Return the right-hand side of the assignment
+    pub fn rhs(&self) -> Option<Expression<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the description of the statement
+    pub fn description(&self) -> Option<Description<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl Expression<'_> {
+    /// Return `true` if the expression is a conditional expression
+    pub fn is_if(&self) -> bool {
+        token(self.cst, self.id, TokenKind::If).is_some()
+    }
+}
+
+impl<'a> TypeSpecifier<'a> {
+    /// Return `true` if the name is looked up in the global scope
+    pub fn is_global(&self) -> bool {
+        token(self.cst, self.id, TokenKind::Dot).is_some()
+    }
+
+    /// Return the name of the type
+    pub fn name(&self) -> Option<Name<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl<'a> Name<'a> {
+    /// Return identifiers constituting the name
+    pub fn parts(&self) -> Vec<&'a str> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Token(tok) if self.cst.tokens().kind(*tok) == TokenKind::Identifier => {
+                    Some(self.cst.tokens().text(*tok))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> ComponentReference<'a> {
+    /// Return `true` if the reference is looked up in the global scope
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self.cst.children(self.id).first(),
+            Some(Child::Token(tok)) if self.cst.tokens().kind(*tok) == TokenKind::Dot
+        )
+    }
+
+    /// Return identifiers constituting the reference
+    pub fn parts(&self) -> Vec<&'a str> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Token(tok) if self.cst.tokens().kind(*tok) == TokenKind::Identifier => {
+                    Some(self.cst.tokens().text(*tok))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> Description<'a> {
+    /// Return the description string
+    pub fn string(&self) -> Option<DescriptionString<'a>> {
+        child(self.cst, self.id)
+    }
+
+    /// Return the annotation
+    pub fn annotation(&self) -> Option<AnnotationClause<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+impl DescriptionString<'_> {
+    /// Return the string tokens concatenated in the description
+    pub fn strings(&self) -> Vec<TokenID> {
+        self.cst
+            .children(self.id)
+            .iter()
+            .filter_map(|c| match c {
+                Child::Token(tok) if self.cst.tokens().kind(*tok) == TokenKind::String => {
+                    Some(*tok)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> AnnotationClause<'a> {
+    /// Return the class modification holding annotation entries
+    pub fn modification(&self) -> Option<ClassModification<'a>> {
+        child(self.cst, self.id)
+    }
+}
+
+/// Return the first child node that can be cast to the given type
+fn child<'a, N: AstNode<'a>>(cst: &'a ModelicaCST, id: TreeID) -> Option<N> {
+    cst.children(id).iter().find_map(|c| match c {
+        Child::Tree(tree) => N::cast(cst, *tree),
+        Child::Token(_) => None,
+    })
+}
+
+/// Return all child nodes that can be cast to the given type
+fn children<'a, N: AstNode<'a>>(cst: &'a ModelicaCST, id: TreeID) -> Vec<N> {
+    cst.children(id)
+        .iter()
+        .filter_map(|c| match c {
+            Child::Tree(tree) => N::cast(cst, *tree),
+            Child::Token(_) => None,
+        })
+        .collect()
+}
+
+/// Return the first child node of the given kind
+fn child_of_kind(cst: &ModelicaCST, id: TreeID, kind: SyntaxKind) -> Option<TreeID> {
+    cst.children(id).iter().find_map(|c| match c {
+        Child::Tree(tree) if cst.kind(*tree) == kind => Some(*tree),
+        _ => None,
+    })
+}
+
+/// Return the first child token of the given kind
+fn token(cst: &ModelicaCST, id: TreeID, kind: TokenKind) -> Option<TokenID> {
+    cst.children(id).iter().find_map(|c| match c {
+        Child::Token(tok) if cst.tokens().kind(*tok) == kind => Some(*tok),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::EntryPoint;
+
+    fn parse(code: &str) -> ModelicaCST {
+        ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        )
+    }
+
+    #[test]
+    fn test_class_definition() {
+        let cst = parse(
+            "within Foo;\npartial model Bar \"Bar model\"\n  extends Baz(x = 1);\n  parameter Real x[2](each start = 0) = {1, 2} \"Foo\";\nequation\n  der(x) = -x;\n  annotation (Icon());\nend Bar;",
+        );
+        let root = StoredDefinition::cast(&cst, cst.root().unwrap()).unwrap();
+        assert_eq!(root.within().unwrap().text(), "Foo");
+        let class = root.class_definitions()[0];
+        assert_eq!(class.name(), Some("Bar"));
+        assert_eq!(class.end_name(), Some("Bar"));
+        assert!(class.prefixes().unwrap().is_partial());
+        assert_eq!(
+            class.prefixes().unwrap().restriction(),
+            Some(TokenKind::Model)
+        );
+        assert_eq!(class.description().unwrap().text(), "\"Bar model\"");
+        assert!(class.annotation().is_some());
+        let composition = class.composition().unwrap();
+        let elements = composition.elements();
+        assert_eq!(elements.len(), 2);
+        let extends = elements[0].extends_clause().unwrap();
+        assert_eq!(extends.type_specifier().unwrap().text(), "Baz");
+        let clause = elements[1].component_clause().unwrap();
+        assert_eq!(
+            clause.prefixes().unwrap().kinds(),
+            vec![TokenKind::Parameter]
+        );
+        let component = clause.declarations()[0];
+        assert_eq!(component.name(), Some("x"));
+        let modification = component.modification().unwrap();
+        assert_eq!(modification.expression().unwrap().text(), "{1, 2}");
+        let start = modification.class_modification().unwrap().find("start");
+        assert_eq!(
+            start
+                .unwrap()
+                .modification()
+                .unwrap()
+                .expression()
+                .unwrap()
+                .text(),
+            "0"
+        );
+        let description = component.description().unwrap().string().unwrap();
+        assert_eq!(description.text(), "\"Foo\"");
+        let equations = composition.equation_sections()[0].equations();
+        assert_eq!(equations.len(), 1);
+        assert_eq!(equations[0].kind(), Some(SyntaxKind::SimpleExpression));
+        assert_eq!(equations[0].rhs().unwrap().text(), "-x");
+    }
+
+    #[test]
+    fn test_short_class_definition() {
+        let cst = parse("type Foo = Real(unit = \"m\") \"Length\" annotation ();");
+        let root = StoredDefinition::cast(&cst, cst.root().unwrap()).unwrap();
+        let class = root.class_definitions()[0];
+        assert_eq!(class.name(), Some("Foo"));
+        assert_eq!(class.end_name(), None);
+        assert!(class.composition().is_none());
+        assert!(class.modification().unwrap().find("unit").is_some());
+        assert_eq!(class.description().unwrap().text(), "\"Length\"");
+        assert!(class.annotation().is_some());
+    }
+
+    #[test]
+    fn test_imports() {
+        let cst = parse("model Foo\n  import SI = Modelica.Units.SI;\n  import Modelica.Math.{sin, cos};\n  import Modelica.Constants.*;\nend Foo;");
+        let root = StoredDefinition::cast(&cst, cst.root().unwrap()).unwrap();
+        let elements = root.class_definitions()[0]
+            .composition()
+            .unwrap()
+            .elements();
+        let imports: Vec<ImportClause> =
+            elements.iter().filter_map(|e| e.import_clause()).collect();
+        assert_eq!(imports[0].alias(), Some("SI"));
+        assert_eq!(imports[0].name().unwrap().text(), "Modelica.Units.SI");
+        assert_eq!(imports[1].imported_names(), vec!["sin", "cos"]);
+        assert!(imports[2].is_wildcard());
+    }
+}
@@ -1,8 +1,10 @@
-use super::parsing::{SyntaxEvent, SyntaxKind, events};
+use std::ops::Range;
+
+use super::parsing::{EntryPoint, SyntaxEvent, SyntaxKind, events};
 use super::lexing::lex;
 use super::tokens::{TokenID, Tokens};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// A unique identifier for a tree node.
 /// It acts as a opaque pointer to the node in the CST.
 pub struct TreeID(usize);
@@ -13,16 +15,22 @@ pub struct TreeID(usize);
 /// CST API is opaque and does not expose the internal structure of the tree.
 /// It provides methods to navigate the tree and access its nodes.
 pub struct ModelicaCST {
+    entry: EntryPoint,
     tokens: Tokens,
     trees: Vec<Tree>,
     errors: Vec<Error>,
+    /// Nodes that contain tokens, indexed by token slots
+    owners: Vec<Option<TreeID>>,
+    /// Arena slots of nodes detached from the tree
+    free: Vec<TreeID>,
 }
 
 impl ModelicaCST {
-    pub(super) fn new(tokens: Tokens, events: Vec<SyntaxEvent>) -> Self {
+    pub(super) fn new(entry: EntryPoint, tokens: Tokens, events: Vec<SyntaxEvent>) -> Self {
         let mut trees = Vec::new();
         let mut stack: Vec<TreeID> = Vec::new();
         let mut errors = Vec::new();
+        let mut owners = vec![None; tokens.capacity()];
         for event in events {
             match event {
                 SyntaxEvent::Enter(kind) => {
@@ -39,7 +47,9 @@ impl ModelicaCST {
                     }
                 }
                 SyntaxEvent::Advance(id) => {
-                    trees[stack.last().unwrap().0].push(Child::Token(id));
+                    let owner = *stack.last().unwrap();
+                    trees[owner.0].push(Child::Token(id));
+                    owners[id.index()] = Some(owner);
                 }
                 SyntaxEvent::Error(i, msg) => {
                     errors.push(Error {
@@ -50,9 +60,12 @@ impl ModelicaCST {
             }
         }
         ModelicaCST {
+            entry,
             tokens,
             trees,
             errors,
+            owners,
+            free: Vec::new(),
         }
     }
 
@@ -65,10 +78,15 @@ impl ModelicaCST {
     /// 
     /// # Returns
     /// A `ModelicaCST` object representing the parsed code.
-    pub fn from(source: String, code: String, entry: SyntaxKind) -> Self {
+    pub fn from(source: String, code: String, entry: EntryPoint) -> Self {
         let tokens = lex(source, code);
         let events = events(&tokens, entry);
-        Self::new(tokens, events)
+        Self::new(entry, tokens, events)
+    }
+
+    /// Return the entry point used to parse the code.
+    pub fn entry(&self) -> EntryPoint {
+        self.entry
     }
 
     /// Return the root node of the CST.
@@ -85,11 +103,10 @@ impl ModelicaCST {
         self.errors
             .iter()
             .map(|e| {
-
-                let pos = if e.token == self.tokens.last() {
-                    self.tokens.end(e.token)
-                } else {
-                    self.tokens.start(e.token)
+                let pos = match e.token {
+                    Some(tok) if tok == self.tokens.last() => self.tokens.end(tok),
+                    Some(tok) => self.tokens.start(tok),
+                    None => self.tokens.position_at(self.tokens.code().len()),
                 };
                 format!(
                     "{}:{}:{}: {}",
@@ -102,6 +119,29 @@ impl ModelicaCST {
             .collect()
     }
 
+    /// Return the source code reconstructed from tokens of the tree
+    /// and trivia attached to them. For trees produced from the whole
+    /// input it reproduces the input byte for byte.
+    pub fn to_source(&self) -> String {
+        let mut code = String::new();
+        match self.root() {
+            Some(root) if !self.is_empty(root) => {
+                for tok in self.tokens_in(root) {
+                    for trivia in self.tokens.leading_trivia(tok) {
+                        code += self.tokens.trivia_text(&trivia);
+                    }
+                    code += self.tokens.text(tok);
+                    for trivia in self.tokens.trailing_trivia(tok) {
+                        code += self.tokens.trivia_text(&trivia);
+                    }
+                }
+            }
+            // Input that contains only trivia
+            _ => code += self.tokens.code(),
+        }
+        code
+    }
+
     /// Return the reference to the tokens API
     pub fn tokens(&self) -> &Tokens {
         &self.tokens
@@ -145,33 +185,268 @@ impl ModelicaCST {
 
     /// Return `true` if the node spans multiple lines.
     pub fn is_multiline(&self, id: TreeID) -> bool {
-        let first = self.tokens.get(self.start(id));
-        let last = self.tokens.get(self.end(id));
-        first.start.line < last.end.line
+        self.tokens.start(self.start(id)).line < self.tokens.end(self.end(id)).line
+    }
+
+    /// Return the byte range of the node in the source code. Trivia
+    /// surrounding the node is not included.
+    pub fn text_range(&self, id: TreeID) -> Range<usize> {
+        self.tokens.range(self.start(id)).start..self.tokens.range(self.end(id)).end
+    }
+
+    /// Return the source code of the node.
+    pub fn text(&self, id: TreeID) -> &str {
+        &self.tokens.code()[self.text_range(id)]
+    }
+
+    /// Return the innermost node that contains the given byte offset.
+    ///
+    /// Return `None` if the offset lies past the end of the code or
+    /// inside a character.
+    pub fn node_at_offset(&self, offset: usize) -> Option<TreeID> {
+        let c = self.tokens.code().get(offset..)?.chars().next()?;
+        self.innermost_node(offset..offset + c.len_utf8())
+    }
+
+    /// Return the innermost node that entirely covers the given byte
+    /// range.
+    ///
+    /// Return `None` if the range is reversed, or if its bounds lie past
+    /// the end of the code or inside characters.
+    pub fn covering_node(&self, range: Range<usize>) -> Option<TreeID> {
+        self.tokens.code().get(range.clone())?;
+        self.innermost_node(range)
+    }
+
+    fn innermost_node(&self, range: Range<usize>) -> Option<TreeID> {
+        let covers = |id: TreeID| {
+            let r = self.text_range(id);
+            r.start <= range.start && range.end <= r.end
+        };
+        let end = |child: &Child| match child {
+            Child::Token(tok) => self.tokens.range(*tok).end,
+            Child::Tree(tree) => self.tokens.range(self.end(*tree)).end,
+        };
+        let mut node = self.root().filter(|r| !self.is_empty(*r) && covers(*r))?;
+        // Children are ordered, so only the first one that doesn't end
+        // before the range can cover it
+        loop {
+            let children = self.children(node);
+            match children.get(children.partition_point(|c| end(c) < range.end)) {
+                Some(Child::Tree(tree)) if covers(*tree) => node = *tree,
+                _ => return Some(node),
+            }
+        }
     }
 
     /// Return `true` if the node of the given id contains a child node of the given kind.
     pub fn contains(&self, id: TreeID, kind: SyntaxKind) -> bool {
-        let mut contains = false;
-        for child in self.children(id).iter() {
-            if let Child::Tree(tree) = child {
-                if self.kind(*tree) == kind {
-                    contains = true;
-                } else {
-                    contains = self.contains(*tree, kind);
+        self.descendants(id).skip(1).any(|t| self.kind(t) == kind)
+    }
+
+    /// Return an iterator over the node of the given id and all its
+    /// descendants in preorder.
+    pub fn descendants(&self, id: TreeID) -> Descendants<'_> {
+        Descendants {
+            cst: self,
+            stack: vec![id],
+        }
+    }
+
+    /// Return an iterator over the node of the given id and all its
+    /// ancestors, ending at the root.
+    pub fn ancestors(&self, id: TreeID) -> Ancestors<'_> {
+        Ancestors {
+            cst: self,
+            next: Some(id),
+        }
+    }
+
+    /// Return an iterator over other child nodes of the parent of the
+    /// node of the given id.
+    pub fn siblings(&self, id: TreeID) -> impl Iterator<Item = TreeID> + '_ {
+        self.parent(id)
+            .map_or(&[] as &[Child], |p| self.children(p))
+            .iter()
+            .filter_map(move |c| match c {
+                Child::Tree(tree) if *tree != id => Some(*tree),
+                _ => None,
+            })
+    }
+
+    /// Return an iterator over all tokens constituting the node of the
+    /// given id.
+    pub fn tokens_in(&self, id: TreeID) -> TokensIn<'_> {
+        TokensIn {
+            cst: self,
+            stack: vec![self.children(id).iter()],
+        }
+    }
+}
+
+impl ModelicaCST {
+    /// Replace the node with the root of the tree that was parsed from
+    /// its updated source code. The root keeps the id of the node, and
+    /// nodes of the replaced subtree are reused. Return the id.
+    ///
+    /// Tokens from the first token of the node to `last` are replaced
+    /// with `tokens` lexed from the code updated with the edit. Tokens
+    /// of the `tree` must match the leading part of `tokens`. If `last`
+    /// is a part of another node, it must match the last of `tokens`.
+    pub(super) fn graft(
+        &mut self,
+        id: TreeID,
+        tree: ModelicaCST,
+        tokens: Tokens,
+        last: TokenID,
+        edit: Range<usize>,
+        text: &str,
+    ) -> TreeID {
+        let first = self.start(id);
+        let offset = self.tokens.range(first).start;
+        // Node that contains `last` and the position of the token in it
+        let link = self.owners[last.index()]
+            .filter(|o| self.ancestors(*o).all(|t| t != id))
+            .map(|o| {
+                let children = self.children(o);
+                (o, children.iter().position(|c| matches!(c, Child::Token(t) if *t == last)))
+            });
+        let detached: Vec<TreeID> = self.descendants(id).skip(1).collect();
+        for t in detached {
+            self.trees[t.0].children.clear();
+            self.free.push(t);
+        }
+        self.trees[id.0].children.clear();
+
+        let ModelicaCST { trees, owners, .. } = self;
+        let new = self
+            .tokens
+            .splice(edit, text, first..=last, tokens, offset, |from, to| {
+                relink(trees, owners, from, to)
+            });
+        if self.owners.len() < self.tokens.capacity() {
+            self.owners.resize(self.tokens.capacity(), None);
+        }
+        let slots = new.start.index()..new.end.index();
+        self.owners[slots.clone()].fill(None);
+        if let Some((owner, Some(position))) = link {
+            let sentinel = TokenID::from_index(slots.end - 1);
+            self.trees[owner.0].children[position] = Child::Token(sentinel);
+            self.owners[slots.end - 1] = Some(owner);
+        }
+
+        // Empty nodes are not attached to the tree, so they are skipped
+        let attached: Vec<TreeID> = tree
+            .root()
+            .map_or(Vec::new(), |r| tree.descendants(r).collect());
+        let mut ids = vec![id; tree.trees.len()];
+        for t in attached.iter().skip(1) {
+            ids[t.0] = self.free.pop().unwrap_or_else(|| {
+                self.trees.push(Tree::new(SyntaxKind::Error, None));
+                TreeID(self.trees.len() - 1)
+            });
+        }
+        let parent = self.parent(id);
+        let mut trees: Vec<Option<Tree>> = tree.trees.into_iter().map(Some).collect();
+        for t in attached {
+            let mut node = trees[t.0].take().unwrap();
+            node.parent = if t.0 == 0 { parent } else { node.parent.map(|p| ids[p.0]) };
+            for child in node.children.iter_mut() {
+                match child {
+                    Child::Token(tok) => {
+                        *tok = TokenID::from_index(slots.start + tok.index());
+                        self.owners[tok.index()] = Some(ids[t.0]);
+                    }
+                    Child::Tree(tree) => *tree = ids[tree.0],
                 }
             }
-            if contains {
-                return contains;
+            self.trees[ids[t.0].0] = node;
+        }
+        id
+    }
+
+    /// Return the number of nodes in the arena, including detached ones
+    #[cfg(test)]
+    pub(super) fn arena_size(&self) -> usize {
+        self.trees.len()
+    }
+}
+
+/// Update the reference to the token that was moved to another slot
+fn relink(trees: &mut [Tree], owners: &mut Vec<Option<TreeID>>, from: TokenID, to: TokenID) {
+    if owners.len() <= to.index() {
+        owners.resize(to.index() + 1, None);
+    }
+    let owner = owners[from.index()].take();
+    owners[to.index()] = owner;
+    if let Some(owner) = owner {
+        for child in trees[owner.0].children.iter_mut() {
+            if matches!(child, Child::Token(t) if *t == from) {
+                *child = Child::Token(to);
+            }
+        }
+    }
+}
+
+/// Iterator over the node and its descendants in preorder
+pub struct Descendants<'a> {
+    cst: &'a ModelicaCST,
+    stack: Vec<TreeID>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = TreeID;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        for child in self.cst.children(id).iter().rev() {
+            if let Child::Tree(tree) = child {
+                self.stack.push(*tree);
+            }
+        }
+        Some(id)
+    }
+}
+
+/// Iterator over the node and its ancestors
+pub struct Ancestors<'a> {
+    cst: &'a ModelicaCST,
+    next: Option<TreeID>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = TreeID;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.cst.parent(id);
+        Some(id)
+    }
+}
+
+/// Iterator over tokens constituting the node
+pub struct TokensIn<'a> {
+    cst: &'a ModelicaCST,
+    stack: Vec<std::slice::Iter<'a, Child>>,
+}
+
+impl Iterator for TokensIn<'_> {
+    type Item = TokenID;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(Child::Token(tok)) => return Some(*tok),
+                Some(Child::Tree(tree)) => self.stack.push(self.cst.children(*tree).iter()),
+                None => _ = self.stack.pop(),
             }
         }
-        contains
     }
 }
 
 struct Error {
     msg: String,
-    token: TokenID,
+    token: Option<TokenID>,
 }
 
 struct Tree {
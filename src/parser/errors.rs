@@ -0,0 +1,30 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents lexical and syntax errors detected in the source code.
+///
+/// Every error is described by a message in the
+/// `source:line:col: description` form.
+pub struct ParseErrors {
+    messages: Vec<String>,
+}
+
+impl ParseErrors {
+    pub(super) fn new(messages: Vec<String>) -> Self {
+        ParseErrors { messages }
+    }
+
+    /// Return messages describing the errors
+    pub fn messages(&self) -> &[String] {
+        self.messages.as_slice()
+    }
+}
+
+impl Display for ParseErrors {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.messages.join("\n"))
+    }
+}
+
+impl Error for ParseErrors {}
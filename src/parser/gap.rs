@@ -0,0 +1,217 @@
+use std::ops::Range;
+
+/// Value that contains byte offsets in the source code
+pub(super) trait Offsets: Copy {
+    /// Return the value with every offset `o` replaced with `f(o)`
+    fn map(self, f: impl Fn(usize) -> usize) -> Self;
+}
+
+impl Offsets for usize {
+    fn map(self, f: impl Fn(usize) -> usize) -> Self {
+        f(self)
+    }
+}
+
+/// Ordered sequence of values with a gap placed where the code was
+/// most recently edited.
+///
+/// Offsets in values that follow the gap are stored relative to the end
+/// of the code, so edits made at the gap don't require updating them.
+/// Values are addressed by slots, which don't change unless the value
+/// is moved when the gap is relocated or grown.
+pub(super) struct GapBuffer<T> {
+    slots: Vec<T>,
+    gap: Range<usize>,
+}
+
+impl<T: Offsets> GapBuffer<T> {
+    pub(super) fn new() -> Self {
+        GapBuffer::from(Vec::new())
+    }
+
+    pub(super) fn from(values: Vec<T>) -> Self {
+        let len = values.len();
+        GapBuffer {
+            slots: values,
+            gap: len..len,
+        }
+    }
+
+    /// Append the value. The gap must be placed at the end.
+    pub(super) fn push(&mut self, value: T) {
+        debug_assert!(self.gap.end == self.slots.len());
+        self.slots.truncate(self.gap.start);
+        self.slots.push(value);
+        self.gap = self.slots.len()..self.slots.len();
+    }
+
+    /// Return the number of values
+    pub(super) fn len(&self) -> usize {
+        self.slots.len() - self.gap.len()
+    }
+
+    /// Return the number of slots, including the ones in the gap
+    pub(super) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return the slot of the value at the given index
+    pub(super) fn slot(&self, index: usize) -> usize {
+        if index < self.gap.start {
+            index
+        } else {
+            index + self.gap.len()
+        }
+    }
+
+    /// Return the index of the value in the given slot
+    pub(super) fn index(&self, slot: usize) -> usize {
+        if slot < self.gap.start {
+            slot
+        } else {
+            slot - self.gap.len()
+        }
+    }
+
+    /// Return the slot of the value that follows the one in the given
+    /// slot
+    pub(super) fn next(&self, slot: usize) -> Option<usize> {
+        let next = if slot + 1 == self.gap.start {
+            self.gap.end
+        } else {
+            slot + 1
+        };
+        (next < self.slots.len()).then_some(next)
+    }
+
+    /// Return the slot of the value that precedes the one in the given
+    /// slot
+    pub(super) fn prev(&self, slot: usize) -> Option<usize> {
+        match slot {
+            0 => None,
+            s if s == self.gap.end && self.gap.start == 0 => None,
+            s if s == self.gap.end => Some(self.gap.start - 1),
+            s => Some(s - 1),
+        }
+    }
+
+    /// Return an iterator over occupied slots in order
+    pub(super) fn slots(&self) -> impl DoubleEndedIterator<Item = usize> {
+        (0..self.gap.start).chain(self.gap.end..self.slots.len())
+    }
+
+    /// Return the value in the given slot. `len` is the length of the
+    /// code.
+    pub(super) fn get(&self, slot: usize, len: usize) -> T {
+        if slot < self.gap.start {
+            self.slots[slot]
+        } else {
+            self.slots[slot].map(|o| len - o)
+        }
+    }
+
+    /// Store the value in the given slot. `len` is the length of the
+    /// code.
+    pub(super) fn set(&mut self, slot: usize, value: T, len: usize) {
+        self.slots[slot] = if slot < self.gap.start {
+            value
+        } else {
+            value.map(|o| len - o)
+        };
+    }
+
+    /// Return the index of the first value for which the predicate is
+    /// false. Values must be partitioned by the predicate.
+    pub(super) fn partition_point(&self, len: usize, pred: impl Fn(T) -> bool) -> usize {
+        let before = self.slots[..self.gap.start].partition_point(|v| pred(*v));
+        if before < self.gap.start {
+            return before;
+        }
+        before + self.slots[self.gap.end..].partition_point(|v| pred(v.map(|o| len - o)))
+    }
+
+    /// Replace values in the index range with the new ones, given with
+    /// absolute offsets. `len` is the length of the code before the
+    /// edit, and `moved` is called with the old and the new slot of
+    /// every value that was moved. Return slots of the new values.
+    pub(super) fn replace(
+        &mut self,
+        range: Range<usize>,
+        values: Vec<T>,
+        len: usize,
+        mut moved: impl FnMut(usize, usize),
+    ) -> Range<usize> {
+        self.move_gap(range.end, len, &mut moved);
+        self.gap.start -= range.len();
+        if let Some(filler) = values.first().filter(|_| self.gap.len() < values.len()) {
+            // Grow the gap at least twice, so the cost is amortized
+            let extra = values.len().max(self.slots.len());
+            let end = self.gap.end;
+            self.slots
+                .splice(end..end, std::iter::repeat_n(*filler, extra));
+            for slot in (end..self.slots.len() - extra).rev() {
+                moved(slot, slot + extra);
+            }
+            self.gap.end += extra;
+        }
+        let start = self.gap.start;
+        for value in values {
+            self.slots[self.gap.start] = value;
+            self.gap.start += 1;
+        }
+        start..self.gap.start
+    }
+
+    /// Move the gap in front of the value at the given index
+    fn move_gap(&mut self, index: usize, len: usize, moved: &mut impl FnMut(usize, usize)) {
+        let size = self.gap.len();
+        // Offsets are converted between absolute and relative to the end
+        // of the code in both directions by the same subtraction
+        while self.gap.start > index {
+            let slot = self.gap.start - 1;
+            self.slots[slot + size] = self.slots[slot].map(|o| len - o);
+            moved(slot, slot + size);
+            self.gap = slot..slot + size;
+        }
+        while self.gap.start < index {
+            let slot = self.gap.end;
+            self.slots[self.gap.start] = self.slots[slot].map(|o| len - o);
+            moved(slot, self.gap.start);
+            self.gap = self.gap.start + 1..slot + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gap_buffer() {
+        let values = |buffer: &GapBuffer<usize>, len| {
+            buffer
+                .slots()
+                .map(|s| buffer.get(s, len))
+                .collect::<Vec<_>>()
+        };
+        // Line starts of "a\nbb\nc" with "bb\n" replaced by "x\ny\nz"
+        let mut buffer = GapBuffer::from(vec![0, 2, 5]);
+        let mut moves = Vec::new();
+        let slots = buffer.replace(2..3, vec![4, 6], 6, |a, b| moves.push((a, b)));
+        assert_eq!(slots, 2..4);
+        assert!(moves.is_empty());
+        assert_eq!(values(&buffer, 8), vec![0, 2, 4, 6]);
+        assert_eq!(buffer.partition_point(8, |l| l <= 4), 3);
+        // Line break inserted at the beginning moves the gap back and
+        // makes offsets of the following values relative
+        let slots = buffer.replace(1..1, vec![1], 8, |a, b| moves.push((a, b)));
+        assert_eq!(slots, 1..2);
+        assert_eq!(moves, vec![(3, 5), (2, 4), (1, 3)]);
+        assert_eq!(values(&buffer, 9), vec![0, 1, 3, 5, 7]);
+        assert_eq!(buffer.partition_point(9, |l| l <= 5), 4);
+        assert_eq!((buffer.len(), buffer.capacity()), (5, 6));
+        assert_eq!((buffer.slot(2), buffer.index(3)), (3, 2));
+        assert_eq!((buffer.next(1), buffer.prev(3)), (Some(3), Some(1)));
+        assert_eq!((buffer.next(5), buffer.prev(0)), (None, None));
+    }
+}
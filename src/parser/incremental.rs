@@ -0,0 +1,259 @@
+use std::ops::Range;
+
+use super::cst::{ModelicaCST, TreeID};
+use super::lexing::lex;
+use super::parsing::{EntryPoint, SyntaxKind};
+use super::tokens::TokenKind;
+
+impl ModelicaCST {
+    /// Replace the byte range of the source code with the text and
+    /// update the tree accordingly. Return the id of the reparsed node.
+    ///
+    /// Only the smallest element, equation or statement enclosing the
+    /// edit is relexed and reparsed. If there is no such node, or it
+    /// can't be reparsed in isolation, the whole code is parsed again.
+    /// Apart from updating the source string, the work done depends on
+    /// the size of the reparsed node and the distance from the previous
+    /// edit, not on the size of the code.
+    /// Token and node IDs obtained before the edit shouldn't be used
+    /// after it.
+    pub fn apply_edit(&mut self, range: Range<usize>, text: &str) -> Option<TreeID> {
+        if let Some(id) = self.reparse_node(range.clone(), text) {
+            return Some(id);
+        }
+        let mut code = String::from(self.tokens().code());
+        code.replace_range(range, text);
+        *self = ModelicaCST::from(String::from(self.tokens().source()), code, self.entry());
+        self.root()
+    }
+
+    /// Try to reparse the smallest node that encloses the edit. Return
+    /// `None` if it is impossible.
+    fn reparse_node(&mut self, range: Range<usize>, text: &str) -> Option<TreeID> {
+        let tokens = self.tokens();
+        if tokens.has_errors() || !self.errors().is_empty() {
+            return None;
+        }
+        let node = self
+            .ancestors(self.covering_node(range.clone())?)
+            .find(|t| {
+                matches!(
+                    self.kind(*t),
+                    SyntaxKind::Element | SyntaxKind::Equation | SyntaxKind::Statement
+                )
+            })?;
+        let kind = self.kind(node);
+        let old = self.text_range(node);
+
+        // The region also covers the next significant token, so it is
+        // possible to check if the edit didn't affect the following code,
+        // and if the node still ends before that token
+        let mut sentinel = self.tokens().next(self.end(node));
+        while let Some(tok) = sentinel {
+            if tokens.kind(tok) >= TokenKind::Comma {
+                break;
+            }
+            sentinel = tokens.next(tok);
+        }
+        let region_end = sentinel.map_or(tokens.code().len(), |s| tokens.range(s).end);
+        let code = tokens.code();
+        let region = format!(
+            "{}{}{}",
+            &code[old.start..range.start],
+            text,
+            &code[range.end..region_end]
+        );
+        let relexed = lex(String::from(tokens.source()), region.clone());
+        if relexed.has_errors() {
+            return None;
+        }
+        let significant = relexed.tokens();
+        if let Some(sentinel) = sentinel {
+            let last = *significant.last()?;
+            let start = tokens.range(sentinel).start + text.len() - range.len() - old.start;
+            if relexed.kind(last) != tokens.kind(sentinel)
+                || relexed.range(last) != (start..region.len())
+            {
+                return None;
+            }
+        }
+
+        // Rules may accept the end of file in place of the missing
+        // tokens, so the node is parsed along with the next token and it
+        // has to stop exactly on it, like in the whole code
+        let tree = ModelicaCST::from(
+            String::from(tokens.source()),
+            region,
+            EntryPoint::try_from(kind).ok()?,
+        );
+        let root = tree.root()?;
+        if !tree.errors().is_empty()
+            || tree.is_empty(root)
+            || tree.kind(root) != kind
+            || tree.tokens_in(root).count() + sentinel.iter().count() != significant.len()
+        {
+            return None;
+        }
+        let last = sentinel.unwrap_or(tokens.last());
+        Some(self.graft(node, tree, relexed, last, range, text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Trivia;
+
+    const CODE: &str = "model Foo\n  Real x = 1; // comment\n  Real y;\nequation\n  x = y + 2;\n  if x > 1 then\n    y = 2;\n  end if;\nend Foo;\n";
+
+    fn parse(code: &str) -> ModelicaCST {
+        ModelicaCST::from(
+            String::from("test"),
+            code.to_string(),
+            EntryPoint::StoredDefinition,
+        )
+    }
+
+    /// Return the description of the tree reachable from the root
+    fn dump(cst: &ModelicaCST) -> Vec<String> {
+        let tokens = cst.tokens();
+        let mut dump: Vec<String> = tokens
+            .all()
+            .into_iter()
+            .map(|t| format!("{:?} {:?}", tokens.kind(t), tokens.range(t)))
+            .collect();
+        if let Some(root) = cst.root() {
+            dump.extend(cst.descendants(root).map(|t| {
+                let depth = cst.ancestors(t).count();
+                format!("{depth} {:?} {:?}", cst.kind(t), cst.text(t))
+            }));
+        }
+        dump
+    }
+
+    /// Apply the edit incrementally and compare the result with the
+    /// fresh parse. Return the kind of the reparsed node.
+    fn check(range: Range<usize>, text: &str) -> SyntaxKind {
+        let mut cst = parse(CODE);
+        let id = cst.apply_edit(range.clone(), text).unwrap();
+        let mut code = CODE.to_string();
+        code.replace_range(range, text);
+        let fresh = parse(&code);
+        assert_eq!(cst.to_source(), code);
+        assert_eq!(cst.tokens().code(), code);
+        assert_eq!(cst.pretty_print(), fresh.pretty_print());
+        assert_eq!(cst.errors(), fresh.errors());
+        assert_eq!(dump(&cst), dump(&fresh));
+        // Token IDs are not preserved, so trivia is compared by text
+        let trivia = |cst: &ModelicaCST| {
+            let tokens = cst.tokens();
+            tokens
+                .tokens()
+                .into_iter()
+                .map(|tok| {
+                    let text = |trivia: Vec<Trivia>| {
+                        trivia
+                            .iter()
+                            .map(|t| tokens.trivia_text(t).to_string())
+                            .collect::<Vec<_>>()
+                    };
+                    (text(tokens.leading_trivia(tok)), text(tokens.trailing_trivia(tok)))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(trivia(&cst), trivia(&fresh));
+        cst.kind(id)
+    }
+
+    #[test]
+    fn test_reparse_element() {
+        // Replace `1` with `2 * z`
+        assert_eq!(check(21..22, "2 * z"), SyntaxKind::Element);
+        // Rename `y`
+        assert_eq!(check(42..43, "foo"), SyntaxKind::Element);
+        // Insert a comment inside the element
+        assert_eq!(check(42..42, "/* Y */ "), SyntaxKind::Element);
+    }
+
+    #[test]
+    fn test_reparse_equation() {
+        // Replace `y + 2` with `3`
+        assert_eq!(check(60..65, "3"), SyntaxKind::Equation);
+        // Edit the equation nested in the if-equation
+        assert_eq!(check(87..88, "x"), SyntaxKind::Equation);
+        // Break the nested equation into many lines
+        assert_eq!(check(88..88, "\n      "), SyntaxKind::Equation);
+    }
+
+    #[test]
+    fn test_reparse_statement() {
+        let code = "function Foo\nalgorithm\n  x := 1;\n  y := x;\nend Foo;";
+        let mut cst = parse(code);
+        let id = cst.apply_edit(30..31, "2 + x").unwrap();
+        assert_eq!(cst.kind(id), SyntaxKind::Statement);
+        assert_eq!(
+            cst.to_source(),
+            "function Foo\nalgorithm\n  x := 2 + x;\n  y := x;\nend Foo;"
+        );
+        let fresh = parse(&cst.to_source());
+        assert_eq!(cst.pretty_print(), fresh.pretty_print());
+    }
+
+    #[test]
+    fn test_invalid_edit() {
+        let code = "function Foo\nalgorithm\n  x := y;\n  y := x;\nend Foo;";
+        let mut cst = parse(code);
+        // Reference `y.` is incomplete only because `;` follows it
+        let id = cst.apply_edit(31..31, ".").unwrap();
+        assert_eq!(cst.kind(id), SyntaxKind::StoredDefinition);
+        let fresh = parse(&cst.to_source());
+        assert!(!fresh.errors().is_empty());
+        assert_eq!(cst.errors(), fresh.errors());
+    }
+
+    #[test]
+    fn test_repeated_edits() {
+        let mut cst = parse(CODE);
+        let mut code = CODE.to_string();
+        let mut sizes = Vec::new();
+        for _ in 0..50 {
+            // Edits of the first element and of the nested equation move
+            // the gap back and forth
+            for (range, text, kind) in [
+                (21..22, "2 * z", SyntaxKind::Element),
+                (91..92, "y + 3 * z", SyntaxKind::Equation),
+                (91..100, "y", SyntaxKind::Equation),
+                (21..26, "1", SyntaxKind::Element),
+            ] {
+                let id = cst.apply_edit(range.clone(), text).unwrap();
+                assert_eq!(cst.kind(id), kind);
+                code.replace_range(range, text);
+                assert_eq!(dump(&cst), dump(&parse(&code)));
+            }
+            assert_eq!(code, CODE);
+            sizes.push((cst.arena_size(), cst.tokens().capacity()));
+        }
+        // Detached nodes and unoccupied token slots are reused
+        assert!(sizes.iter().all(|s| *s == sizes[0]));
+        let fresh = parse(CODE);
+        assert!(sizes[0].0 <= 2 * fresh.arena_size());
+        assert!(sizes[0].1 <= 2 * fresh.tokens().capacity());
+    }
+
+    #[test]
+    fn test_full_reparse() {
+        // Edit spans many elements
+        assert_eq!(check(12..43, "Integer z"), SyntaxKind::StoredDefinition);
+        // Edit outside of any element
+        assert_eq!(check(6..9, "Bar"), SyntaxKind::StoredDefinition);
+        // Line comment swallows the semicolon
+        assert_eq!(check(21..21, "// "), SyntaxKind::StoredDefinition);
+        // Element turns into two elements
+        assert_eq!(check(21..22, "1; Real z"), SyntaxKind::StoredDefinition);
+        // Unclosed string
+        let mut cst = parse(CODE);
+        let id = cst.apply_edit(21..22, "\"").unwrap();
+        assert_eq!(cst.kind(id), SyntaxKind::StoredDefinition);
+        assert!(!cst.tokens().errors().is_empty());
+    }
+}
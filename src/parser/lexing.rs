@@ -22,6 +22,8 @@
 ///
 /// - Illegal characters.
 /// - Unclosed strings or comments.
+/// - Numeric literals with malformed exponents.
+/// - Escape sequences in strings that are not defined by the specification.
 ///
 /// Errors are stored as special kind of tokens, and the lexer continues processing the input.
 ///
@@ -31,6 +33,9 @@
 /// and quoted identifiers, and it ensures that tokens are aligned with character boundaries.
 use super::tokens::{TokenKind, Tokens};
 
+/// Characters that may follow a backslash in a string literal
+pub(super) const ESCAPED: &str = "'\"?\\abfnrtv";
+
 /// Tokenizes the given Modelica source code.
 ///
 /// The `lex` function is the main entry point for lexical analysis of Modelica source code.
@@ -39,6 +44,7 @@ use super::tokens::{TokenKind, Tokens};
 pub fn lex(name: String, source: String) -> Tokens {
     let mut lexer = Lexer::new(name, source);
     lexer.tokenize();
+    lexer.tokens.attach_trivia();
     lexer.tokens
 }
 
@@ -81,16 +87,16 @@ impl Lexer {
         }
     }
 
-    /// Return next byte from the input without consuming it
-    fn peek(&mut self) -> Option<u8> {
-        self.tokens.code().as_bytes().get(self.current).cloned()
+    /// Return next character from the input without consuming it
+    fn peek(&mut self) -> Option<char> {
+        self.tokens.code()[self.current..].chars().next()
     }
 
-    /// Return next byte from the input and consume it
-    fn next(&mut self) -> Option<u8> {
+    /// Return next character from the input and consume it
+    fn next(&mut self) -> Option<char> {
         match self.peek() {
             Some(c) => {
-                self.current += 1;
+                self.current += c.len_utf8();
                 Some(c)
             }
             None => {
@@ -109,18 +115,13 @@ impl Lexer {
     /// Update the starting position for building the next token
     #[inline(always)]
     fn jump(&mut self) {
-        // If the current position is not a char boundary, we need to
-        // find the next char boundary
-        while !self.tokens.code().is_char_boundary(self.current) {
-            self.current += 1;
-        }
         self.start = self.current;
     }
 
     /// Return `true` if character is valid and consume it
-    fn accept(&mut self, s: &[u8]) -> bool {
+    fn accept(&mut self, s: &str) -> bool {
         if let Some(c) = self.peek() {
-            if s.contains(&c) {
+            if s.contains(c) {
                 self.next();
                 return true;
             }
@@ -128,47 +129,52 @@ impl Lexer {
         false
     }
 
-    /// Consume a sequence of digits
+    /// Consume a sequence of digits. Return `true` if any digit was
+    /// consumed.
     #[inline(always)]
-    fn accept_digits(&mut self) {
+    fn accept_digits(&mut self) -> bool {
+        let start = self.current;
         while let Some(c) = self.peek() {
             if !c.is_ascii_digit() {
-                return;
+                break;
             }
             self.next();
         }
+        self.current > start
     }
 
     /// Top-level lexing procedure
     fn lex_source(&mut self) {
         if let Some(c) = self.next() {
             match c {
-                b';' => self.push_token(TokenKind::Semicolon),
-                b',' => self.push_token(TokenKind::Comma),
-                b'+' => self.push_token(TokenKind::Plus),
-                b'-' => self.push_token(TokenKind::Minus),
-                b'*' => self.push_token(TokenKind::Star),
-                b'^' => self.push_token(TokenKind::Flex),
-                b'(' => self.push_token(TokenKind::LParen),
-                b'{' => self.push_token(TokenKind::LCurly),
-                b'[' => self.push_token(TokenKind::LBracket),
-                b')' => self.push_token(TokenKind::RParen),
-                b'}' => self.push_token(TokenKind::RCurly),
-                b']' => self.push_token(TokenKind::RBracket),
-                b':' => self.lex_colon(),
-                b'=' => self.lex_equal(),
-                b'<' => self.lex_lesser(),
-                b'>' => self.lex_greater(),
-                b'.' => self.lex_dot(),
-                b'"' => self.lex_string(),
-                b'\'' => self.lex_qident(),
-                b'/' => self.lex_slash(),
+                ';' => self.push_token(TokenKind::Semicolon),
+                ',' => self.push_token(TokenKind::Comma),
+                '+' => self.push_token(TokenKind::Plus),
+                '-' => self.push_token(TokenKind::Minus),
+                '*' => self.push_token(TokenKind::Star),
+                '^' => self.push_token(TokenKind::Flex),
+                '(' => self.push_token(TokenKind::LParen),
+                '{' => self.push_token(TokenKind::LCurly),
+                '[' => self.push_token(TokenKind::LBracket),
+                ')' => self.push_token(TokenKind::RParen),
+                '}' => self.push_token(TokenKind::RCurly),
+                ']' => self.push_token(TokenKind::RBracket),
+                ':' => self.lex_colon(),
+                '=' => self.lex_equal(),
+                '<' => self.lex_lesser(),
+                '>' => self.lex_greater(),
+                '.' => self.lex_dot(),
+                '"' => self.lex_string(),
+                '\'' => self.lex_qident(),
+                '/' => self.lex_slash(),
+                // Byte order mark is kept in the leading whitespace
+                '\u{feff}' if self.start == 0 => self.lex_space(),
                 _ => {
                     if c.is_ascii_whitespace() {
                         return self.lex_space();
                     } else if c.is_ascii_digit() {
                         return self.lex_numeral();
-                    } else if c.is_ascii_alphabetic() || c == b'_' {
+                    } else if c.is_ascii_alphabetic() || c == '_' {
                         return self.lex_nondigit();
                     }
                     self.push_token(TokenKind::ErrorIllegalCharacter);
@@ -179,7 +185,7 @@ impl Lexer {
 
     /// Scan the slice that starts with `:`
     fn lex_colon(&mut self) {
-        if self.accept(&[b'=']) {
+        if self.accept("=") {
             return self.push_token(TokenKind::Assign);
         }
         self.push_token(TokenKind::Colon)
@@ -187,7 +193,7 @@ impl Lexer {
 
     /// Scan the slice that starts with `=`
     fn lex_equal(&mut self) {
-        if self.accept(&[b'=']) {
+        if self.accept("=") {
             return self.push_token(TokenKind::Eq);
         }
         self.push_token(TokenKind::Equal)
@@ -195,9 +201,9 @@ impl Lexer {
 
     /// Scan the slice that starts with `<`
     fn lex_lesser(&mut self) {
-        if self.accept(&[b'>']) {
+        if self.accept(">") {
             self.push_token(TokenKind::Neq)
-        } else if self.accept(&[b'=']) {
+        } else if self.accept("=") {
             self.push_token(TokenKind::Leq)
         } else {
             self.push_token(TokenKind::Les)
@@ -206,7 +212,7 @@ impl Lexer {
 
     /// Scan the slice that starts with `>`
     fn lex_greater(&mut self) {
-        if self.accept(&[b'=']) {
+        if self.accept("=") {
             return self.push_token(TokenKind::Geq);
         }
         self.push_token(TokenKind::Gre)
@@ -214,15 +220,15 @@ impl Lexer {
 
     /// Scan the slice that starts with `.`
     fn lex_dot(&mut self) {
-        if self.accept(&[b'+']) {
+        if self.accept("+") {
             self.push_token(TokenKind::DotPlus)
-        } else if self.accept(&[b'-']) {
+        } else if self.accept("-") {
             self.push_token(TokenKind::DotMinus)
-        } else if self.accept(&[b'*']) {
+        } else if self.accept("*") {
             self.push_token(TokenKind::DotStar)
-        } else if self.accept(&[b'/']) {
+        } else if self.accept("/") {
             self.push_token(TokenKind::DotSlash)
-        } else if self.accept(&[b'^']) {
+        } else if self.accept("^") {
             self.push_token(TokenKind::DotFlex)
         } else {
             self.push_token(TokenKind::Dot)
@@ -231,11 +237,16 @@ impl Lexer {
 
     /// Scan the slice that is supposed to be a string
     fn lex_string(&mut self) {
+        let mut valid = true;
         while let Some(c) = self.next() {
             match c {
-                // Skip the escaped character
-                b'\\' => _ = self.next(),
-                b'"' => return self.push_token(TokenKind::String),
+                '\\' => {
+                    if let Some(c) = self.next() {
+                        valid &= ESCAPED.contains(c);
+                    }
+                }
+                '"' if valid => return self.push_token(TokenKind::String),
+                '"' => return self.push_token(TokenKind::ErrorInvalidEscape),
                 _ => (),
             }
         }
@@ -244,13 +255,13 @@ impl Lexer {
 
     /// Scan the slice that is supposed to be a quoted identifier
     fn lex_qident(&mut self) {
-        const ALLOWED: &[u8] = "!#$%&()*+,-./:;<>=?@[]^{}|~ \"".as_bytes();
+        const ALLOWED: &str = "!#$%&()*+,-./:;<>=?@[]^{}|~ \"";
         while let Some(c) = self.next() {
             match c {
-                b'\\' => _ = self.next(),
-                b'\'' => return self.push_token(TokenKind::Identifier),
+                '\\' => _ = self.next(),
+                '\'' => return self.push_token(TokenKind::Identifier),
                 _ => {
-                    if !(c.is_ascii_alphanumeric() || c == b'_' || ALLOWED.contains(&c)) {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || ALLOWED.contains(c)) {
                         return self.push_token(TokenKind::ErrorIllegalQident);
                     }
                 }
@@ -263,8 +274,8 @@ impl Lexer {
     fn lex_slash(&mut self) {
         if let Some(c) = self.peek() {
             match c {
-                b'/' => self.lex_linecomment(),
-                b'*' => self.lex_blockcomment(),
+                '/' => self.lex_linecomment(),
+                '*' => self.lex_blockcomment(),
                 _ => self.push_token(TokenKind::Slash),
             }
         } else {
@@ -276,7 +287,7 @@ impl Lexer {
     fn lex_linecomment(&mut self) {
         while let Some(c) = self.peek() {
             match c {
-                b'\r' | b'\n' => return self.push_token(TokenKind::LineComment),
+                '\r' | '\n' => return self.push_token(TokenKind::LineComment),
                 _ => _ = self.next(),
             }
         }
@@ -286,9 +297,9 @@ impl Lexer {
     /// Scan the slice that is supposed to be a block comment
     fn lex_blockcomment(&mut self) {
         while let Some(c) = self.next() {
-            if c == b'*' {
+            if c == '*' {
                 if let Some(c) = self.peek() {
-                    if c == b'/' {
+                    if c == '/' {
                         self.next();
                         return self.push_token(TokenKind::BlockComment);
                     }
@@ -316,18 +327,15 @@ impl Lexer {
     /// Scan the slice that is supposed to be a numeral
     fn lex_numeral(&mut self) {
         self.accept_digits();
-        if !self.accept(&[b'.']) {
-            if !self.accept(&[b'e', b'E']) {
-                return self.push_token(TokenKind::UInt);
-            }
-            self.accept(&[b'+', b'-']);
-            self.accept_digits();
-            return self.push_token(TokenKind::UReal);
+        if !self.accept(".") && !self.peek().is_some_and(|c| c == 'e' || c == 'E') {
+            return self.push_token(TokenKind::UInt);
         }
         self.accept_digits();
-        if self.accept(&[b'e', b'E']) {
-            self.accept(&[b'+', b'-']);
-            self.accept_digits();
+        if self.accept("eE") {
+            self.accept("+-");
+            if !self.accept_digits() {
+                return self.push_token(TokenKind::ErrorInvalidNumber);
+            }
         }
         self.push_token(TokenKind::UReal)
     }
@@ -335,7 +343,7 @@ impl Lexer {
     /// Scan the slice that is supposed to be an indentifier or a keyword
     fn lex_nondigit(&mut self) {
         while let Some(c) = self.peek() {
-            if !(c.is_ascii_alphanumeric() || c == b'_') {
+            if !(c.is_ascii_alphanumeric() || c == '_') {
                 break;
             }
             self.next();
@@ -399,6 +407,22 @@ impl Lexer {
             "each" => self.push_token(TokenKind::Each),
             "annotation" => self.push_token(TokenKind::Annotation),
             "external" => self.push_token(TokenKind::External),
+            #[cfg(feature = "optimica")]
+            "optimization" => self.push_token(TokenKind::Optimization),
+            #[cfg(feature = "optimica")]
+            "constraint" => self.push_token(TokenKind::Constraint),
+            #[cfg(feature = "metamodelica")]
+            "uniontype" => self.push_token(TokenKind::Uniontype),
+            #[cfg(feature = "metamodelica")]
+            "match" => self.push_token(TokenKind::Match),
+            #[cfg(feature = "metamodelica")]
+            "matchcontinue" => self.push_token(TokenKind::MatchContinue),
+            #[cfg(feature = "metamodelica")]
+            "case" => self.push_token(TokenKind::Case),
+            #[cfg(feature = "metamodelica")]
+            "local" => self.push_token(TokenKind::Local),
+            #[cfg(feature = "metamodelica")]
+            "guard" => self.push_token(TokenKind::Guard),
             "true" | "false" => self.push_token(TokenKind::Bool),
             _ => self.push_token(TokenKind::Identifier),
         }
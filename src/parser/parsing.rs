@@ -3,19 +3,63 @@ use std::cell::Cell;
 use super::tokens::{TokenID, TokenKind, Tokens};
 
 /// Return a vector of syntax events for the given tokenized input
-pub fn events(tokens: &Tokens, start: SyntaxKind) -> Vec<SyntaxEvent> {
+pub fn events(tokens: &Tokens, start: EntryPoint) -> Vec<SyntaxEvent> {
     let mut parser = Parser::new(tokens);
-    parser.parse(start);
+    parser.parse(SyntaxKind::from(start));
     parser.events
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-/// Represents Modelica grammar rules as defined in [Modelica
-/// Specification
-/// 3.7](https://specification.modelica.org/maint/3.7/modelica-concrete-syntax.html).
-pub enum SyntaxKind {
-    /// Custom production type used to indicate a faulty syntax tree
-    Error,
+/// Define `SyntaxKind` and `EntryPoint` enums from the single list of
+/// grammar rules, and conversions between both enums
+macro_rules! syntax_kinds {
+    ($($(#[doc = $doc:literal])* $(#[cfg($cfg:meta)])? $kind:ident),* $(,)?) => {
+        #[derive(Debug, PartialEq, Clone, Copy)]
+        /// Represents Modelica grammar rules as defined in [Modelica
+        /// Specification
+        /// 3.7](https://specification.modelica.org/maint/3.7/modelica-concrete-syntax.html).
+        pub enum SyntaxKind {
+            /// Custom production type used to indicate a faulty syntax tree
+            Error,
+            $($(#[doc = $doc])* $(#[cfg($cfg)])? $kind,)*
+        }
+
+        #[derive(Debug, PartialEq, Clone, Copy)]
+        /// Represents Modelica grammar rules that can be used as entry
+        /// points for parsing. These are all `SyntaxKind` variants except
+        /// for the `Error`.
+        pub enum EntryPoint {
+            $($(#[doc = $doc])* $(#[cfg($cfg)])? $kind,)*
+        }
+
+        impl EntryPoint {
+            /// All entry points in the order of their definition
+            pub const ALL: &'static [EntryPoint] = &[$($(#[cfg($cfg)])? EntryPoint::$kind,)*];
+        }
+
+        impl From<EntryPoint> for SyntaxKind {
+            fn from(entry: EntryPoint) -> Self {
+                match entry {
+                    $($(#[cfg($cfg)])? EntryPoint::$kind => SyntaxKind::$kind,)*
+                }
+            }
+        }
+
+        impl TryFrom<SyntaxKind> for EntryPoint {
+            type Error = SyntaxKind;
+
+            /// Return the entry point corresponding to the syntax kind,
+            /// or the kind itself if it can't be parsed
+            fn try_from(kind: SyntaxKind) -> Result<Self, Self::Error> {
+                match kind {
+                    SyntaxKind::Error => Err(kind),
+                    $($(#[cfg($cfg)])? SyntaxKind::$kind => Ok(EntryPoint::$kind),)*
+                }
+            }
+        }
+    };
+}
+
+syntax_kinds!(
     StoredDefinition,
     ClassDefinition,
     ClassPrefixes,
@@ -103,7 +147,18 @@ pub enum SyntaxKind {
     Description,
     DescriptionString,
     AnnotationClause,
-}
+    /// Optimica `constraint` section
+    #[cfg(feature = "optimica")]
+    ConstraintSection,
+    /// MetaModelica `match` or `matchcontinue` expression
+    #[cfg(feature = "metamodelica")]
+    MatchExpression,
+    /// MetaModelica `case` or `else` branch of the `match` expression
+    #[cfg(feature = "metamodelica")]
+    MatchCase,
+    /// Modelica script (`.mos`), that is not a part of the specification
+    Script,
+);
 
 #[derive(Debug)]
 /// Represents a single Modelica syntax event.
@@ -115,7 +170,7 @@ pub enum SyntaxEvent {
     Enter(SyntaxKind),
     Exit,
     Advance(TokenID),
-    Error(TokenID, String),
+    Error(Option<TokenID>, String),
 }
 
 /// Represents a Modelica parser
@@ -227,7 +282,14 @@ impl<'a> Parser<'a> {
             SyntaxKind::Description => description(self),
             SyntaxKind::DescriptionString => description_string(self),
             SyntaxKind::AnnotationClause => annotation_clause(self),
-            SyntaxKind::Error => panic!("cannot parse error node"),
+            #[cfg(feature = "optimica")]
+            SyntaxKind::ConstraintSection => constraint_section(self),
+            #[cfg(feature = "metamodelica")]
+            SyntaxKind::MatchExpression => match_expression(self),
+            #[cfg(feature = "metamodelica")]
+            SyntaxKind::MatchCase => match_case(self),
+            SyntaxKind::Script => script(self),
+            SyntaxKind::Error => unreachable!("error is not an entry point"),
         }
     }
 
@@ -259,9 +321,13 @@ impl<'a> Parser<'a> {
         self.events.push(SyntaxEvent::Exit);
     }
 
-    /// Advance the parser, consume the token and push it into the events vector
+    /// Advance the parser, consume the token and push it into the events vector.
+    /// Report an error instead if parser reached the end of file.
     fn advance(&mut self) {
-        assert!(!self.eof());
+        if self.eof() {
+            self.error(String::from("unexpected end of file"));
+            return;
+        }
         self.events
             .push(SyntaxEvent::Advance(*self.indices.get(self.pos).unwrap()));
         self.pos += 1;
@@ -320,19 +386,31 @@ impl<'a> Parser<'a> {
         false
     }
 
+    /// Return the state of the parser that can be restored with `rewind`
+    fn checkpoint(&self) -> (usize, usize) {
+        (self.pos, self.events.len())
+    }
+
+    /// Restore the state of the parser saved with `checkpoint`
+    fn rewind(&mut self, checkpoint: (usize, usize)) {
+        self.pos = checkpoint.0;
+        self.events.truncate(checkpoint.1);
+    }
+
     /// Mark currently parsed token as erroneus.
     fn error(&mut self, msg: String) {
-        self.events.push(SyntaxEvent::Error(
-            *self
-                .indices
-                .get(self.pos)
-                .unwrap_or_else(|| self.indices.last().unwrap()),
-            msg,
-        ));
+        // There are no tokens to point at if the input is empty
+        let tok = self.indices.get(self.pos).or(self.indices.last());
+        self.events.push(SyntaxEvent::Error(tok.copied(), msg));
     }
 
     /// Advance the parser and mark the current token as erroneus.
+    /// At the end of file only report the error.
     fn advance_with_error(&mut self, msg: String) {
+        if self.eof() {
+            self.error(msg);
+            return;
+        }
         let mark = self.enter();
         self.error(msg);
         self.advance();
@@ -352,7 +430,7 @@ impl<'a> Parser<'a> {
 
 
 // Keywords that are used to break sections in the Modelica code
-const SECTION_BREAKERS: [TokenKind; 8] = [
+const SECTION_BREAKERS: &[TokenKind] = &[
     TokenKind::Protected,
     TokenKind::Public,
     TokenKind::Initial,
@@ -361,10 +439,12 @@ const SECTION_BREAKERS: [TokenKind; 8] = [
     TokenKind::End,
     TokenKind::Annotation,
     TokenKind::External,
+    #[cfg(feature = "optimica")]
+    TokenKind::Constraint,
 ];
 
 // Keywords that are used to define classes
-const CLASS_PREFS: [TokenKind; 13] = [
+const CLASS_PREFS: &[TokenKind] = &[
     TokenKind::Partial,
     TokenKind::Class,
     TokenKind::Model,
@@ -378,6 +458,10 @@ const CLASS_PREFS: [TokenKind; 13] = [
     TokenKind::Pure,
     TokenKind::Impure,
     TokenKind::Expandable,
+    #[cfg(feature = "optimica")]
+    TokenKind::Optimization,
+    #[cfg(feature = "metamodelica")]
+    TokenKind::Uniontype,
 ];
 
 // A.2.1 Stored Definition – Within
@@ -423,6 +507,10 @@ fn class_prefixes(p: &mut Parser) {
         | TokenKind::Function => {
             p.advance();
         }
+        #[cfg(feature = "optimica")]
+        TokenKind::Optimization => p.advance(),
+        #[cfg(feature = "metamodelica")]
+        TokenKind::Uniontype => p.advance(),
         TokenKind::Expandable => {
             p.advance();
             p.expect(TokenKind::Connector);
@@ -476,6 +564,11 @@ fn long_class_specifier(p: &mut Parser) {
         }
     } else {
         p.expect(TokenKind::Identifier);
+        // Optimica allows modifications of optimization classes
+        #[cfg(feature = "optimica")]
+        if p.check(TokenKind::LParen) {
+            class_modification(p);
+        }
     }
     description_string(p);
     composition(p);
@@ -574,6 +667,10 @@ fn composition(p: &mut Parser) {
             TokenKind::Algorithm => {
                 algorithm_section(p);
             }
+            #[cfg(feature = "optimica")]
+            TokenKind::Constraint => {
+                constraint_section(p);
+            }
             _ => p.advance_with_error(
                 format!(
                     "unexpected token '{:?}' after element list inside composition. Expected 'protected', 'public', 'initial', 'equation', 'algorithm', 'external', 'annotation' or 'end'.",
@@ -624,7 +721,7 @@ fn external_function_call(p: &mut Parser) {
 
 fn element_list(p: &mut Parser) {
     let mark = p.enter();
-    while !p.check_any(&SECTION_BREAKERS) && !p.eof() {
+    while !p.check_any(SECTION_BREAKERS) && !p.eof() {
         element(p);
         p.expect(TokenKind::Semicolon);
     }
@@ -643,7 +740,7 @@ fn element(p: &mut Parser) {
         p.consume(TokenKind::Inner);
         p.consume(TokenKind::Outer);
         if p.consume(TokenKind::Replaceable) {
-            if p.check_any(&CLASS_PREFS) || p.check(TokenKind::Encapsulated) {
+            if p.check_any(CLASS_PREFS) || p.check(TokenKind::Encapsulated) {
                 class_definition(p);
             } else {
                 component_clause(p);
@@ -652,7 +749,7 @@ fn element(p: &mut Parser) {
                 constraining_clause(p);
                 description(p);
             }
-        } else if p.check_any(&CLASS_PREFS) || p.check(TokenKind::Encapsulated) {
+        } else if p.check_any(CLASS_PREFS) || p.check(TokenKind::Encapsulated) {
             class_definition(p);
         } else {
             component_clause(p);
@@ -897,7 +994,7 @@ fn element_redeclaration(p: &mut Parser) {
     p.expect(TokenKind::Redeclare);
     p.consume(TokenKind::Each);
     p.consume(TokenKind::Final);
-    if p.check_any(&CLASS_PREFS) {
+    if p.check_any(CLASS_PREFS) {
         short_class_definition(p);
     } else if p.check(TokenKind::Replaceable) {
         element_replaceable(p);
@@ -910,7 +1007,7 @@ fn element_redeclaration(p: &mut Parser) {
 fn element_replaceable(p: &mut Parser) {
     let mark = p.enter();
     p.expect(TokenKind::Replaceable);
-    if p.check_any(&CLASS_PREFS) {
+    if p.check_any(CLASS_PREFS) {
         short_class_definition(p);
     } else {
         component_clause1(p);
@@ -949,7 +1046,7 @@ fn equation_section(p: &mut Parser) {
     let mark = p.enter();
     p.consume(TokenKind::Initial);
     p.expect(TokenKind::Equation);
-    while !p.check_any(&SECTION_BREAKERS) && !p.eof() {
+    while !p.check_any(SECTION_BREAKERS) && !p.eof() {
         equation(p);
         p.expect(TokenKind::Semicolon);
     }
@@ -960,7 +1057,7 @@ fn algorithm_section(p: &mut Parser) {
     let mark = p.enter();
     p.consume(TokenKind::Initial);
     p.expect(TokenKind::Algorithm);
-    while !p.check_any(&SECTION_BREAKERS) && !p.eof() {
+    while !p.check_any(SECTION_BREAKERS) && !p.eof() {
         statement(p);
         p.expect(TokenKind::Semicolon);
     }
@@ -1212,6 +1309,8 @@ fn expression(p: &mut Parser) {
             p.expect(TokenKind::Else);
             expression(p);
         }
+        #[cfg(feature = "metamodelica")]
+        TokenKind::Match | TokenKind::MatchContinue => match_expression(p),
         _ => simple_expression(p),
     }
     p.exit(mark, SyntaxKind::Expression);
@@ -1388,6 +1487,15 @@ fn type_specifier(p: &mut Parser) {
     let mark = p.enter();
     p.consume(TokenKind::Dot);
     name(p);
+    // MetaModelica type parameters, like `list<Integer>`
+    #[cfg(feature = "metamodelica")]
+    if p.consume(TokenKind::Les) {
+        type_specifier(p);
+        while p.consume(TokenKind::Comma) && !p.eof() {
+            type_specifier(p);
+        }
+        p.expect(TokenKind::Gre);
+    }
     p.exit(mark, SyntaxKind::TypeSpecifier);
 }
 
@@ -1613,3 +1721,129 @@ fn annotation_clause(p: &mut Parser) {
     class_modification(p);
     p.exit(mark, SyntaxKind::AnnotationClause);
 }
+
+// Optimica Extension
+
+#[cfg(feature = "optimica")]
+fn constraint_section(p: &mut Parser) {
+    let mark = p.enter();
+    p.expect(TokenKind::Constraint);
+    while !p.check_any(SECTION_BREAKERS) && !p.eof() {
+        equation(p);
+        p.expect(TokenKind::Semicolon);
+    }
+    p.exit(mark, SyntaxKind::ConstraintSection);
+}
+
+// MetaModelica Extension
+
+#[cfg(feature = "metamodelica")]
+fn match_expression(p: &mut Parser) {
+    let mark = p.enter();
+    let kind = if p.check(TokenKind::MatchContinue) {
+        TokenKind::MatchContinue
+    } else {
+        TokenKind::Match
+    };
+    p.expect(kind);
+    expression(p);
+    description_string(p);
+    if p.consume(TokenKind::Local) {
+        let list = p.enter();
+        while !p.check_any(&[TokenKind::Case, TokenKind::Else, TokenKind::End]) && !p.eof() {
+            element(p);
+            p.expect(TokenKind::Semicolon);
+        }
+        p.exit(list, SyntaxKind::ElementList);
+    }
+    while p.check_any(&[TokenKind::Case, TokenKind::Else]) {
+        match_case(p);
+    }
+    p.expect(TokenKind::End);
+    p.expect(kind);
+    p.exit(mark, SyntaxKind::MatchExpression);
+}
+
+#[cfg(feature = "metamodelica")]
+fn match_case(p: &mut Parser) {
+    let mark = p.enter();
+    if !p.consume(TokenKind::Else) {
+        p.expect(TokenKind::Case);
+        expression(p);
+        if p.consume(TokenKind::Guard) {
+            expression(p);
+        }
+    }
+    if p.consume(TokenKind::Equation) {
+        while !p.check(TokenKind::Then) && !p.eof() {
+            equation(p);
+            p.expect(TokenKind::Semicolon);
+        }
+    } else if p.consume(TokenKind::Algorithm) {
+        while !p.check(TokenKind::Then) && !p.eof() {
+            statement(p);
+            p.expect(TokenKind::Semicolon);
+        }
+    }
+    p.expect(TokenKind::Then);
+    expression(p);
+    p.expect(TokenKind::Semicolon);
+    p.exit(mark, SyntaxKind::MatchCase);
+}
+
+// Modelica Scripts
+
+fn script(p: &mut Parser) {
+    let mark = p.enter();
+    while !p.eof() {
+        match p.nth(0) {
+            TokenKind::Import => import_clause(p),
+            TokenKind::Final
+            | TokenKind::Encapsulated
+            | TokenKind::Partial
+            | TokenKind::Class
+            | TokenKind::Model
+            | TokenKind::Block
+            | TokenKind::Type
+            | TokenKind::Package
+            | TokenKind::Record
+            | TokenKind::Connector
+            | TokenKind::Expandable
+            | TokenKind::Operator
+            | TokenKind::Function => {
+                p.consume(TokenKind::Final);
+                class_definition(p);
+            }
+            TokenKind::Pure | TokenKind::Impure if p.nth(1) != TokenKind::LParen => {
+                class_definition(p)
+            }
+            TokenKind::If
+            | TokenKind::For
+            | TokenKind::While
+            | TokenKind::When
+            | TokenKind::Break
+            | TokenKind::Return => statement(p),
+            _ => {
+                // Parsed expression can be the left-hand side of the
+                // assignment, which is parsed again as the statement
+                let checkpoint = p.checkpoint();
+                expression(p);
+                if p.check(TokenKind::Assign) {
+                    p.rewind(checkpoint);
+                    statement(p);
+                } else if p.checkpoint().0 == checkpoint.0 {
+                    // Skip the token that can't start any statement, so
+                    // the parser always moves forward
+                    p.rewind(checkpoint);
+                    p.advance_with_error(format!(
+                        "unexpected token {:?} in the script",
+                        p.nth(0)
+                    ));
+                    continue;
+                }
+            }
+        }
+        p.expect(TokenKind::Semicolon);
+    }
+    p.exit(mark, SyntaxKind::Script);
+}
@@ -1,4 +1,8 @@
 use std::fmt::{Debug, Error, Formatter};
+use std::ops::{Range, RangeInclusive};
+
+use super::gap::{GapBuffer, Offsets};
+use super::lexing::ESCAPED;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd)]
 #[repr(u8)]
@@ -15,6 +19,8 @@ pub enum TokenKind {
     ErrorUnclosedString,
     ErrorUnclosedBlockComment,
     ErrorUnclosedQIdent,
+    ErrorInvalidNumber,
+    ErrorInvalidEscape,
 
     LineComment,
     BlockComment,
@@ -104,6 +110,27 @@ pub enum TokenKind {
     End,
     Der,
     Connect,
+
+    // Optimica extension
+    #[cfg(feature = "optimica")]
+    Optimization,
+    #[cfg(feature = "optimica")]
+    Constraint,
+
+    // MetaModelica extension
+    #[cfg(feature = "metamodelica")]
+    Uniontype,
+    #[cfg(feature = "metamodelica")]
+    Match,
+    #[cfg(feature = "metamodelica")]
+    MatchContinue,
+    #[cfg(feature = "metamodelica")]
+    Case,
+    #[cfg(feature = "metamodelica")]
+    Local,
+    #[cfg(feature = "metamodelica")]
+    Guard,
+
     Identifier,
     String,
     UInt,
@@ -201,6 +228,22 @@ impl Debug for TokenKind {
             TK::End => write!(f, "'end'"),
             TK::Der => write!(f, "'der'"),
             TK::Connect => write!(f, "'connect'"),
+            #[cfg(feature = "optimica")]
+            TK::Optimization => write!(f, "'optimization'"),
+            #[cfg(feature = "optimica")]
+            TK::Constraint => write!(f, "'constraint'"),
+            #[cfg(feature = "metamodelica")]
+            TK::Uniontype => write!(f, "'uniontype'"),
+            #[cfg(feature = "metamodelica")]
+            TK::Match => write!(f, "'match'"),
+            #[cfg(feature = "metamodelica")]
+            TK::MatchContinue => write!(f, "'matchcontinue'"),
+            #[cfg(feature = "metamodelica")]
+            TK::Case => write!(f, "'case'"),
+            #[cfg(feature = "metamodelica")]
+            TK::Local => write!(f, "'local'"),
+            #[cfg(feature = "metamodelica")]
+            TK::Guard => write!(f, "'guard'"),
             TK::LineComment => write!(f, "LINE COMMENT"),
             TK::BlockComment => write!(f, "BLOCK COMMENT"),
             TK::Identifier => write!(f, "IDENTIFIER"),
@@ -228,12 +271,51 @@ pub struct Position {
     pub col: usize,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+/// Represents a unit used to count columns in a line.
+///
+/// `Position::col` is counted in `Char` units. Other tools may expect
+/// different units, e.g. LSP clients use UTF-16 code units by default.
+pub enum ColumnUnit {
+    /// UTF-8 bytes
+    Byte,
+    /// Unicode scalar values
+    Char,
+    /// UTF-16 code units
+    Utf16,
+}
+
+impl ColumnUnit {
+    /// Return the length of the character in this unit
+    fn len(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Byte => c.len_utf8(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 /// Represents a unique identifier for a token.
 /// 
 /// It acts as a opaque pointer to a token in the `Tokenized` collection.
+/// Identifiers are ordered in the same way as tokens, but they aren't
+/// necessarily consecutive.
 pub struct TokenID(usize);
 
+impl TokenID {
+    /// Return the slot of the token in the collection
+    pub(super) fn index(self) -> usize {
+        self.0
+    }
+
+    /// Return the ID of the token in the given slot
+    pub(super) fn from_index(index: usize) -> Self {
+        TokenID(index)
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Represents a token extracted from the Modelica source code.
 ///
@@ -256,6 +338,22 @@ pub struct Token<'a> {
     pub end: Position,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+/// Represents a piece of trivia, i.e. a part of the source code that
+/// carries no syntactic meaning.
+///
+/// Every significant token owns the trivia that surrounds it. Trailing
+/// trivia spans whitespace and comments that follow the token on the
+/// same line, including the line break. Everything else between
+/// significant tokens belongs to the leading trivia of the next one.
+/// Trivia at the end of the input is attached to the last token.
+pub enum Trivia {
+    /// Run of whitespace characters given by its byte range
+    Whitespace(Range<usize>),
+    /// Comment or erroneous token
+    Token(TokenID),
+}
+
 /// Represents a collection of tokenized data extracted from the Modelica source code.
 ///
 /// This structure has opaque API and does not expose the internal structure of the tokenized data.
@@ -263,56 +361,236 @@ pub struct Token<'a> {
 pub struct Tokens {
     source: String,
     code: String,
-    kinds: Vec<TokenKind>,
-    starts: Vec<usize>,
-    ends: Vec<usize>,
+    data: GapBuffer<TokenData>,
+    lines: GapBuffer<usize>,
+    error_count: usize,
+}
+
+#[derive(Copy, Clone)]
+/// Kind of the token and byte offsets of its text and trivia
+struct TokenData {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+    leading: usize,
+    trailing: usize,
+}
+
+impl Offsets for TokenData {
+    fn map(self, f: impl Fn(usize) -> usize) -> Self {
+        TokenData {
+            kind: self.kind,
+            start: f(self.start),
+            end: f(self.end),
+            leading: f(self.leading),
+            trailing: f(self.trailing),
+        }
+    }
+}
+
+impl TokenData {
+    fn new(kind: TokenKind, start: usize, end: usize) -> Self {
+        TokenData {
+            kind,
+            start,
+            end,
+            leading: start,
+            trailing: end,
+        }
+    }
+
+    fn is_significant(&self) -> bool {
+        self.kind >= TokenKind::Comma
+    }
+
+    fn is_error(&self) -> bool {
+        (TokenKind::ErrorIllegalCharacter..=TokenKind::ErrorInvalidEscape).contains(&self.kind)
+    }
 }
 
 impl Tokens {
 
     pub(super) fn new(source: String, code: String) -> Self {
+        let lines = GapBuffer::from(line_starts(&code, 0));
         Tokens {
             source,
             code,
-            kinds: Vec::new(),
-            starts: Vec::new(),
-            ends: Vec::new(),
+            data: GapBuffer::new(),
+            lines,
+            error_count: 0,
         }
     }
 
     pub(super) fn push(&mut self, kind: TokenKind, start: usize, end: usize) {
-        self.kinds.push(kind);
-        self.starts.push(start);
-        self.ends.push(end);
+        let data = TokenData::new(kind, start, end);
+        self.error_count += data.is_error() as usize;
+        self.data.push(data);
+    }
+
+    /// Return the data of the token in the given slot
+    fn data(&self, slot: usize) -> TokenData {
+        self.data.get(slot, self.code.len())
+    }
+
+    /// Attach trivia to significant tokens. Must be called after all
+    /// tokens were pushed.
+    pub(super) fn attach_trivia(&mut self) {
+        self.attach_trivia_in(0..self.data.len());
+    }
+
+    /// Attach trivia to significant tokens in the range of indices.
+    /// Trivia of the preceding tokens must be already attached.
+    fn attach_trivia_in(&mut self, range: Range<usize>) {
+        let len = self.code.len();
+        let mut prev = (0..range.start)
+            .rev()
+            .map(|i| self.data(self.data.slot(i)))
+            .find(|t| t.is_significant())
+            .map_or(0, |t| t.trailing);
+        for i in range {
+            let slot = self.data.slot(i);
+            let mut token = self.data(slot);
+            if !token.is_significant() {
+                continue;
+            }
+            token.leading = prev;
+            token.trailing = match self.next_significant(slot) {
+                Some(_) => self.trailing_end(slot),
+                None => len,
+            };
+            prev = token.trailing;
+            self.data.set(slot, token, len);
+        }
+    }
+
+    /// Return the slot of the first significant token that follows the
+    /// token in the given slot
+    fn next_significant(&self, slot: usize) -> Option<usize> {
+        let mut next = self.data.next(slot);
+        while let Some(n) = next {
+            if self.data(n).is_significant() {
+                break;
+            }
+            next = self.data.next(n);
+        }
+        next
+    }
+
+    /// Return the end of trivia that follows the token in the given slot
+    /// on the same line
+    fn trailing_end(&self, slot: usize) -> usize {
+        let code = self.code.as_bytes();
+        let mut pos = self.data(slot).end;
+        let mut next = self.data.next(slot);
+        loop {
+            while pos < code.len() && matches!(code[pos], b' ' | b'\t' | b'\r') {
+                pos += 1;
+            }
+            if let Some(n) = next {
+                let token = self.data(n);
+                if token.start == pos
+                    && matches!(token.kind, TokenKind::LineComment | TokenKind::BlockComment)
+                {
+                    pos = token.end;
+                    next = self.data.next(n);
+                    // Multiline comment ends the line
+                    if self.text(TokenID(n)).contains('\n') {
+                        return pos;
+                    }
+                    continue;
+                }
+            }
+            if pos < code.len() && code[pos] == b'\n' {
+                pos += 1;
+            }
+            return pos;
+        }
+    }
+
+    /// Replace the byte range of the source code with the text, and
+    /// tokens from `first` to `last` with the new tokens, lexed from
+    /// the updated code starting at `offset`. `moved` is called with
+    /// the old and the new ID of every other token that was moved.
+    /// Return IDs of the new tokens.
+    ///
+    /// Only the line index and trivia around the edit are updated.
+    pub(super) fn splice(
+        &mut self,
+        edit: Range<usize>,
+        text: &str,
+        old: RangeInclusive<TokenID>,
+        new: Tokens,
+        offset: usize,
+        mut moved: impl FnMut(TokenID, TokenID),
+    ) -> Range<TokenID> {
+        let len = self.code.len();
+        let first = self.data.index(old.start().0);
+        let removed = first..self.data.index(old.end().0) + 1;
+        self.error_count -= removed
+            .clone()
+            .filter(|i| self.data(self.data.slot(*i)).is_error())
+            .count();
+        self.error_count += new.error_count;
+        let count = new.data.len();
+        let tokens = new
+            .data
+            .slots()
+            .map(|s| {
+                let t = new.data(s);
+                TokenData::new(t.kind, t.start + offset, t.end + offset)
+            })
+            .collect();
+        let slots = self
+            .data
+            .replace(removed, tokens, len, |a, b| moved(TokenID(a), TokenID(b)));
+
+        let lines = self.lines.partition_point(len, |l| l <= edit.start)
+            ..self.lines.partition_point(len, |l| l <= edit.end);
+        let starts = line_starts(text, edit.start).split_off(1);
+        self.lines.replace(lines, starts, len, |_, _| ());
+        self.code.replace_range(edit, text);
+
+        // Trivia of the preceding token and of the first significant
+        // token after the new ones can change as well
+        let start = (0..first)
+            .rev()
+            .find(|i| self.data(self.data.slot(*i)).is_significant())
+            .unwrap_or(first);
+        let end = (first + count..self.data.len())
+            .find(|i| self.data(self.data.slot(*i)).is_significant())
+            .map_or(self.data.len(), |i| i + 1);
+        self.attach_trivia_in(start..end);
+        TokenID(slots.start)..TokenID(slots.end)
+    }
+
+    /// Return `true` if there are lexical errors
+    pub(super) fn has_errors(&self) -> bool {
+        self.error_count > 0
+    }
+
+    /// Return the number of slots for tokens, including unoccupied ones
+    pub(super) fn capacity(&self) -> usize {
+        self.data.capacity()
     }
 
     /// Return first token ID
     pub fn first(&self) -> TokenID {
-        TokenID(0)
+        TokenID(self.data.slot(0))
     }
 
     /// Return last token ID
     pub fn last(&self) -> TokenID {
-        TokenID(self.kinds.len() - 1)
+        TokenID(self.data.slot(self.data.len() - 1))
     }
 
     /// Return next valid token ID
     pub fn next(&self, id: TokenID) -> Option<TokenID> {
-        let next = id.0 + 1;
-        if next < self.kinds.len() {
-            Some(TokenID(next))
-        } else {
-            None
-        }
+        self.data.next(id.0).map(TokenID)
     }
 
     /// Return previous valid token ID
     pub fn prev(&self, id: TokenID) -> Option<TokenID> {
-        if id.0 > 0 {
-            Some(TokenID(id.0 - 1))
-        } else {
-            None
-        }
+        self.data.prev(id.0).map(TokenID)
     }
 
     /// Return the source/file from which the tokens were extracted
@@ -327,85 +605,189 @@ impl Tokens {
 
     /// Return kind of the token
     pub fn kind(&self, i: TokenID) -> TokenKind {
-        self.kinds[i.0]
+        self.data(i.0).kind
     }
 
     /// Return the text contents of the token
     pub fn text(&self, i: TokenID) -> &str {
-        let start = self.starts[i.0];
-        let end = self.ends[i.0];
-        &self.code[start..end]
+        &self.code[self.range(i)]
+    }
+
+    /// Return the byte range of the token in the source code
+    pub fn range(&self, i: TokenID) -> Range<usize> {
+        let token = self.data(i.0);
+        token.start..token.end
+    }
+
+    /// Return trivia that precedes the significant token
+    pub fn leading_trivia(&self, i: TokenID) -> Vec<Trivia> {
+        let token = self.data(i.0);
+        self.trivia(token.leading..token.start)
+    }
+
+    /// Return trivia that follows the significant token
+    pub fn trailing_trivia(&self, i: TokenID) -> Vec<Trivia> {
+        let token = self.data(i.0);
+        self.trivia(token.end..token.trailing)
+    }
+
+    /// Return the text of the trivia
+    pub fn trivia_text(&self, trivia: &Trivia) -> &str {
+        match trivia {
+            Trivia::Whitespace(range) => &self.code[range.clone()],
+            Trivia::Token(tok) => self.text(*tok),
+        }
+    }
+
+    /// Split the byte range into trivia pieces
+    fn trivia(&self, range: Range<usize>) -> Vec<Trivia> {
+        let mut pieces = Vec::new();
+        let i = self
+            .data
+            .partition_point(self.code.len(), |t| t.start < range.start);
+        let mut slot = (i < self.data.len()).then(|| self.data.slot(i));
+        let mut pos = range.start;
+        while let Some(s) = slot {
+            let token = self.data(s);
+            if token.start >= range.end {
+                break;
+            }
+            if token.start > pos {
+                pieces.push(Trivia::Whitespace(pos..token.start));
+            }
+            pieces.push(Trivia::Token(TokenID(s)));
+            pos = token.end;
+            slot = self.data.next(s);
+        }
+        if pos < range.end {
+            pieces.push(Trivia::Whitespace(pos..range.end));
+        }
+        pieces
     }
 
     /// Return the start position of the token
     pub fn start(&self, i: TokenID) -> Position {
-        let start = self.starts[i.0];
-        let pre = &self.code[..start];
-        let lines: Vec<&str> = pre.split('\n').collect();
-        Position {
-            offset: start,
-            line: lines.len(),
-            col: lines.last().unwrap().chars().count() + 1,
-        }
+        self.position_at(self.data(i.0).start)
     }
 
     /// Return the end position of the token
     pub fn end(&self, i: TokenID) -> Position {
-        let end = self.ends[i.0];
-        let lines: Vec<&str> = self.code[..end].split('\n').collect();
+        self.position_at(self.data(i.0).end)
+    }
+
+    /// Return the position that corresponds with the byte offset.
+    ///
+    /// Return `None` if the offset lies past the end of the code or
+    /// inside a character.
+    pub fn position(&self, offset: usize) -> Option<Position> {
+        self.code.is_char_boundary(offset).then(|| self.position_at(offset))
+    }
+
+    /// Return the position of the offset that lies on the character
+    /// boundary
+    pub(super) fn position_at(&self, offset: usize) -> Position {
         Position {
-            offset: end,
-            line: lines.len(),
-            col: lines.last().unwrap().chars().count() + 1,
+            offset,
+            line: self.line(offset),
+            col: self.column_at(offset, ColumnUnit::Char),
         }
     }
 
+    /// Return the 1-based number of the line that contains the byte offset
+    pub fn line(&self, offset: usize) -> usize {
+        self.lines.partition_point(self.code.len(), |l| l <= offset)
+    }
+
+    /// Return the 1-based column of the byte offset counted in the
+    /// specified units.
+    ///
+    /// Return `None` if the offset lies past the end of the code or
+    /// inside a character.
+    pub fn column(&self, offset: usize, unit: ColumnUnit) -> Option<usize> {
+        self.code.is_char_boundary(offset).then(|| self.column_at(offset, unit))
+    }
+
+    /// Return the column of the offset that lies on the character boundary
+    fn column_at(&self, offset: usize, unit: ColumnUnit) -> usize {
+        let line_start = self.line_start(self.line(offset) - 1).unwrap();
+        let text = &self.code[line_start..offset];
+        match unit {
+            ColumnUnit::Byte => text.len() + 1,
+            _ => text.chars().map(|c| unit.len(c)).sum::<usize>() + 1,
+        }
+    }
+
+    /// Return the byte offset that corresponds with the 1-based line and
+    /// column counted in the specified units.
+    ///
+    /// Return `None` if the line doesn't exist, if the column lies past
+    /// the end of the line, or if it points inside a character.
+    pub fn offset(&self, line: usize, col: usize, unit: ColumnUnit) -> Option<usize> {
+        let start = self.line_start(line.checked_sub(1)?)?;
+        // Line break is not a part of the line
+        let mut end = self.line_start(line).map_or(self.code.len(), |l| l - 1);
+        if self.code[start..end].ends_with('\r') {
+            end -= 1;
+        }
+        let mut remaining = col.checked_sub(1)?;
+        for (i, c) in self.code[start..end].char_indices() {
+            if remaining == 0 {
+                return Some(start + i);
+            }
+            remaining = remaining.checked_sub(unit.len(c))?;
+        }
+        (remaining == 0).then_some(end)
+    }
+
+    /// Return the start of the line of the given 0-based index
+    fn line_start(&self, index: usize) -> Option<usize> {
+        (index < self.lines.len()).then(|| self.lines.get(self.lines.slot(index), self.code.len()))
+    }
+
     /// Return all valid token IDs
     pub fn all(&self) -> Vec<TokenID> {
-        (0..self.kinds.len()).map(TokenID).collect()
+        self.data.slots().map(TokenID).collect()
     }
 
     /// Return all valid token IDs that are not comments
     pub fn tokens(&self) -> Vec<TokenID> {
-        self.kinds
-            .iter()
-            .enumerate()
-            .filter(|(_, k)| **k >= TokenKind::Comma)
-            .map(|(i, _)| TokenID(i))
+        self.all()
+            .into_iter()
+            .filter(|i| self.kind(*i) >= TokenKind::Comma)
             .collect()
     }
 
     /// Return all valid token IDs that are comments
     pub fn comments(&self) -> Vec<TokenID> {
-        self.kinds
-            .iter()
-            .enumerate()
-            .filter(|(_, k)| **k == TokenKind::LineComment || **k == TokenKind::BlockComment)
-            .map(|(i, _)| TokenID(i))
+        self.all()
+            .into_iter()
+            .filter(|i| matches!(self.kind(*i), TokenKind::LineComment | TokenKind::BlockComment))
             .collect()
     }
 
     /// Return formatted lexical error messages
     pub fn errors(&self) -> Vec<String> {
-        self.kinds
-            .iter()
-            .enumerate()
-            .filter(|(_, k)| {
-                (TokenKind::ErrorIllegalCharacter..TokenKind::ErrorUnclosedQIdent).contains(*k)
-            })
-            .map(|(i, k)| {
-                let start = self.start(TokenID(i));
-                let c = self.code.chars().nth(start.offset).unwrap();
-                
-                match *k {
+        self.data
+            .slots()
+            .filter(|s| self.data(*s).is_error())
+            .map(|s| {
+                let token = self.data(s);
+                let start = self.start(TokenID(s));
+                let text = self.text(TokenID(s));
+                match token.kind {
                     TokenKind::ErrorIllegalCharacter => format!(
-                        "{}:{}:{}: illegal character '{c}'",
-                        self.source, start.line, start.col
-                    ),
-                    TokenKind::ErrorIllegalQident => format!(
-                        "{}:{}:{}: illegal character inside quoted identifier '{c}'",
+                        "{}:{}:{}: illegal character '{text}'",
                         self.source, start.line, start.col
                     ),
+                    TokenKind::ErrorIllegalQident => {
+                        // Token ends with the illegal character
+                        let c = text.chars().next_back().unwrap();
+                        let pos = self.position_at(token.end - c.len_utf8());
+                        format!(
+                            "{}:{}:{}: illegal character inside quoted identifier '{c}'",
+                            self.source, pos.line, pos.col
+                        )
+                    }
                     TokenKind::ErrorUnclosedString => format!(
                         "{}:{}:{}: unclosed string literal",
                         self.source, start.line, start.col
@@ -418,6 +800,23 @@ impl Tokens {
                         "{}:{}:{}: unclosed quoted identifier",
                         self.source, start.line, start.col
                     ),
+                    TokenKind::ErrorInvalidNumber => {
+                        // Exponent is the only part that can be malformed
+                        let exponent = text.find(['e', 'E']).unwrap_or(0);
+                        let pos = self.position_at(start.offset + exponent);
+                        format!(
+                            "{}:{}:{}: missing digits in the exponent of numeric literal '{text}'",
+                            self.source, pos.line, pos.col
+                        )
+                    }
+                    TokenKind::ErrorInvalidEscape => {
+                        let (offset, escaped) = invalid_escape(text).unwrap_or((0, '"'));
+                        let pos = self.position_at(start.offset + offset);
+                        format!(
+                            "{}:{}:{}: invalid escape sequence '\\{escaped}'",
+                            self.source, pos.line, pos.col
+                        )
+                    }
                     _ => unreachable!(),
                 }
             })
@@ -425,7 +824,7 @@ impl Tokens {
     }
 
     /// Return the full token instance of a given ID
-    pub fn get(&self, i: TokenID) -> Token {
+    pub fn get(&self, i: TokenID) -> Token<'_> {
         Token {
             kind: self.kind(i),
             text: self.text(i),
@@ -436,3 +835,26 @@ impl Tokens {
         }
     }
 }
+
+/// Return byte offsets at which lines of the code start, shifted by
+/// the given offset
+fn line_starts(code: &str, offset: usize) -> Vec<usize> {
+    std::iter::once(offset)
+        .chain(code.match_indices('\n').map(|(i, _)| offset + i + 1))
+        .collect()
+}
+
+/// Return the offset and the escaped character of the first escape
+/// sequence in the string literal that is not allowed by the specification
+fn invalid_escape(text: &str) -> Option<(usize, char)> {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            let escaped = chars.next()?.1;
+            if !ESCAPED.contains(escaped) {
+                return Some((i, escaped));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,39 @@
+use super::cst::{Child, ModelicaCST, TreeID};
+use super::parsing::SyntaxKind;
+use super::tokens::{TokenID, TokenKind};
+
+/// Represents an object that can be used to traverse the CST.
+///
+/// Every method has an empty default implementation, so visitors only
+/// need to implement callbacks they are interested in.
+pub trait Visitor {
+    /// Called before visiting children of the node. Return `false` to
+    /// skip children of the node.
+    fn enter(&mut self, _cst: &ModelicaCST, _kind: SyntaxKind, _id: TreeID) -> bool {
+        true
+    }
+
+    /// Called after visiting children of the node. It is also called
+    /// if children were skipped.
+    fn exit(&mut self, _cst: &ModelicaCST, _kind: SyntaxKind, _id: TreeID) {}
+
+    /// Called for every token
+    fn token(&mut self, _cst: &ModelicaCST, _kind: TokenKind, _id: TokenID) {}
+}
+
+impl ModelicaCST {
+    /// Traverse the node of the given id and its descendants in
+    /// preorder, calling visitor's callbacks.
+    pub fn walk(&self, id: TreeID, visitor: &mut impl Visitor) {
+        let kind = self.kind(id);
+        if visitor.enter(self, kind, id) {
+            for child in self.children(id) {
+                match child {
+                    Child::Tree(tree) => self.walk(*tree, visitor),
+                    Child::Token(tok) => visitor.token(self, self.tokens().kind(*tok), *tok),
+                }
+            }
+        }
+        visitor.exit(self, kind, id);
+    }
+}
@@ -0,0 +1,171 @@
+use mofmt::EntryPoint;
+
+/// Samples of code for every entry point
+pub const SAMPLES: &[(EntryPoint, &str)] = &[
+    (
+        EntryPoint::StoredDefinition,
+        "within Foo;\nmodel Bar end Bar;",
+    ),
+    (
+        EntryPoint::ClassDefinition,
+        "encapsulated partial model Foo end Foo",
+    ),
+    (EntryPoint::ClassPrefixes, "partial expandable connector"),
+    (EntryPoint::ClassSpecifier, "Foo \"foo\" Real x; end Foo"),
+    (EntryPoint::LongClassSpecifier, "extends Foo(x = 1) end Foo"),
+    (
+        EntryPoint::ShortClassSpecifier,
+        "Foo = input Bar[2](x = 1) \"foo\"",
+    ),
+    (EntryPoint::DerClassSpecifier, "Foo = der(Bar.baz, x, y)"),
+    (EntryPoint::BasePrefix, "output"),
+    (EntryPoint::EnumList, "a \"a\", b"),
+    (EntryPoint::EnumerationLiteral, "a \"a\""),
+    (
+        EntryPoint::Composition,
+        "Real x; protected Real y; equation x = y; external \"C\" foo(x);",
+    ),
+    (EntryPoint::LanguageSpecification, "\"C\""),
+    (EntryPoint::ExternalFunctionCall, "y = foo(x, 1)"),
+    (EntryPoint::ElementList, "Real x; import Foo.*;"),
+    (
+        EntryPoint::Element,
+        "final inner replaceable Real x constrainedby Real",
+    ),
+    (EntryPoint::ImportClause, "import Foo.{Bar, Baz} \"foo\""),
+    (EntryPoint::ImportList, "Bar, Baz"),
+    (
+        EntryPoint::ExtendsClause,
+        "extends Foo(break x) annotation()",
+    ),
+    (EntryPoint::ConstrainingClause, "constrainedby Foo(x = 1)"),
+    (
+        EntryPoint::ClassOrInheritanceModification,
+        "(x = 1, break y)",
+    ),
+    (
+        EntryPoint::ArgumentOrInheritanceModificationList,
+        "x = 1, break connect(a, b)",
+    ),
+    (EntryPoint::InheritanceModification, "break connect(a, b)"),
+    (EntryPoint::ComponentClause, "flow parameter Real[2] x, y"),
+    (EntryPoint::TypePrefix, "stream constant input"),
+    (EntryPoint::ComponentList, "x, y if true"),
+    (EntryPoint::ComponentDeclaration, "x if b \"x\""),
+    (EntryPoint::ConditionAttribute, "if b"),
+    (EntryPoint::Declaration, "x[2](start = 1) = {1, 2}"),
+    (EntryPoint::Modification, "(start = 1) = 2"),
+    (EntryPoint::ModificationExpression, "break"),
+    (EntryPoint::ClassModification, "(each x = 1, final y)"),
+    (EntryPoint::ArgumentList, "x = 1, y"),
+    (EntryPoint::Argument, "redeclare Real x"),
+    (
+        EntryPoint::ElementModificationOrReplaceable,
+        "each final x(y = 1) = 2",
+    ),
+    (EntryPoint::ElementModification, "x.y(z = 1) = 2 \"x\""),
+    (
+        EntryPoint::ElementRedeclaration,
+        "redeclare each type Foo = Bar",
+    ),
+    (
+        EntryPoint::ElementReplaceable,
+        "replaceable Real x constrainedby Real",
+    ),
+    (EntryPoint::ComponentClause1, "parameter Real x = 1"),
+    (EntryPoint::ComponentDeclaration1, "x = 1 \"x\""),
+    (EntryPoint::ShortClassDefinition, "model Foo = Bar"),
+    (
+        EntryPoint::EquationSection,
+        "initial equation x = 1; y = 2;",
+    ),
+    (EntryPoint::AlgorithmSection, "algorithm x := 1; break;"),
+    (EntryPoint::Equation, "x = y \"x\""),
+    (EntryPoint::Statement, "(x, , y) := foo(z)"),
+    (
+        EntryPoint::IfEquation,
+        "if a then x = 1; elseif b then x = 2; else x = 3; end if",
+    ),
+    (
+        EntryPoint::IfStatement,
+        "if a then x := 1; else x := 2; end if",
+    ),
+    (
+        EntryPoint::ForEquation,
+        "for i in 1:n loop x[i] = i; end for",
+    ),
+    (EntryPoint::ForStatement, "for i loop x[i] := i; end for"),
+    (EntryPoint::ForIndices, "i in 1:n, j"),
+    (EntryPoint::ForIndex, "i in 1:n"),
+    (
+        EntryPoint::WhileStatement,
+        "while x < 1 loop x := x + 1; end while",
+    ),
+    (
+        EntryPoint::WhenEquation,
+        "when a then x = 1; elsewhen b then x = 2; end when",
+    ),
+    (
+        EntryPoint::WhenStatement,
+        "when a then reinit(x, 1); end when",
+    ),
+    (EntryPoint::ConnectEquation, "connect(a.b, c[1])"),
+    (EntryPoint::Expression, "if a then b else c"),
+    (EntryPoint::SimpleExpression, "1:2:n"),
+    (EntryPoint::LogicalExpression, "a or b"),
+    (EntryPoint::LogicalTerm, "a and not b"),
+    (EntryPoint::LogicalFactor, "not a"),
+    (EntryPoint::Relation, "a <= b"),
+    (EntryPoint::RelationalOperator, "<>"),
+    (EntryPoint::ArithmeticExpression, "-a + b .- c"),
+    (EntryPoint::AddOperator, ".+"),
+    (EntryPoint::Term, "a * b ./ c"),
+    (EntryPoint::MulOperator, ".*"),
+    (EntryPoint::Factor, "a ^ 2"),
+    (EntryPoint::Primary, "der(x)"),
+    (EntryPoint::TypeSpecifier, ".Modelica.Units.SI.Length"),
+    (EntryPoint::Name, "Modelica.Units"),
+    (EntryPoint::ComponentReference, "a[1].'b c'[2, :]"),
+    (EntryPoint::ResultReference, "der(x, 2)"),
+    (EntryPoint::FunctionCallArgs, "(x, y = 1)"),
+    (EntryPoint::FunctionArguments, "x for x in y"),
+    (EntryPoint::FunctionArgumentsNonFirst, "y, z = 1"),
+    (EntryPoint::ArrayArguments, "1, 2, 3"),
+    (EntryPoint::ArrayArgumentsNonFirst, "2, 3"),
+    (EntryPoint::NamedArguments, "x = 1, y = 2"),
+    (EntryPoint::NamedArgument, "x = 1"),
+    (EntryPoint::FunctionArgument, "function foo(x = 1)"),
+    (
+        EntryPoint::FunctionPartialApplication,
+        "function foo(x = 1)",
+    ),
+    (EntryPoint::OutputExpressionList, "x, , y"),
+    (EntryPoint::ExpressionList, "x, y"),
+    (EntryPoint::ArraySubscripts, "[1, :]"),
+    (EntryPoint::Subscript, ":"),
+    (
+        EntryPoint::Description,
+        "\"foo\" + \"bar\" annotation(x = 1)",
+    ),
+    (EntryPoint::DescriptionString, "\"foo\" + \"bar\""),
+    (
+        EntryPoint::AnnotationClause,
+        "annotation(Icon(graphics = {Line()}))",
+    ),
+    #[cfg(feature = "optimica")]
+    (EntryPoint::ConstraintSection, "constraint x <= 1; y >= 0;"),
+    #[cfg(feature = "metamodelica")]
+    (
+        EntryPoint::MatchExpression,
+        "match x local Integer i; case SOME(i) then i; else then 0; end match",
+    ),
+    #[cfg(feature = "metamodelica")]
+    (
+        EntryPoint::MatchCase,
+        "case (a, _) guard a > 0 equation b = a; then b;",
+    ),
+    (
+        EntryPoint::Script,
+        "import Modelica.Math; loadFile(\"foo.mo\"); x := 1; x + 1;",
+    ),
+];
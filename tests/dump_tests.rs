@@ -0,0 +1,43 @@
+use std::{env, fs};
+
+use mofmt::{EntryPoint, ModelicaCST, SyntaxKind};
+
+mod common;
+
+// Helper functions
+fn parse(code: &str, entry: EntryPoint) -> ModelicaCST {
+    ModelicaCST::from(String::from("test"), String::from(code), entry)
+}
+
+/// Compare the dump with the snapshot stored in `tests/snapshots`.
+/// Snapshots are written instead if `UPDATE_SNAPSHOTS` is set.
+fn check_snapshot(name: &str, dump: &str) {
+    let path = format!("tests/snapshots/{}", name);
+    let dump = format!("{}\n", dump);
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &dump).expect("error");
+    }
+    let expected = fs::read_to_string(&path).expect("error");
+    assert_eq!(expected, dump, "snapshot mismatch: {}", path);
+}
+
+#[test]
+fn test_sexpr_snapshots() {
+    for (entry, code) in common::SAMPLES {
+        let cst = parse(code, *entry);
+        assert!(cst.errors().is_empty(), "{:?}: {:?}", entry, cst.errors());
+        let root = cst.root().unwrap();
+        assert_eq!(cst.kind(root), SyntaxKind::from(*entry));
+        assert_eq!(cst.tokens_in(root).count(), cst.tokens().tokens().len());
+        check_snapshot(&format!("{:?}.txt", entry), &cst.to_sexpr());
+    }
+}
+
+#[test]
+fn test_json_snapshot() {
+    let cst = parse(
+        "model Foo \"it's \\\"foo\\\"\"\n  Real x = 1;\nend Foo;",
+        EntryPoint::StoredDefinition,
+    );
+    check_snapshot("json.txt", &cst.to_json());
+}
@@ -0,0 +1,99 @@
+use mofmt::{EntryPoint, ModelicaCST, SyntaxKind};
+
+mod common;
+
+// Helper functions
+fn parse(code: &str, entry: EntryPoint) -> ModelicaCST {
+    ModelicaCST::from(String::from("test"), String::from(code), entry)
+}
+
+#[test]
+fn test_entry_point_conversions() {
+    for entry in EntryPoint::ALL {
+        let kind = SyntaxKind::from(*entry);
+        assert_eq!(EntryPoint::try_from(kind), Ok(*entry));
+    }
+    assert_eq!(
+        EntryPoint::try_from(SyntaxKind::Error),
+        Err(SyntaxKind::Error)
+    );
+}
+
+#[test]
+fn test_format_entry_points() {
+    assert_eq!(common::SAMPLES.len(), EntryPoint::ALL.len());
+    for (entry, code) in common::SAMPLES {
+        let cst = parse(code, *entry);
+        assert_eq!(cst.entry(), *entry);
+        let formatted = cst.pretty_print();
+        let reparsed = parse(&formatted, *entry);
+        assert!(reparsed.errors().is_empty(), "{:?}: {:?}", entry, formatted);
+    }
+}
+
+#[test]
+fn test_empty_input() {
+    for entry in EntryPoint::ALL {
+        let cst = parse("", *entry);
+        assert_eq!(cst.pretty_print(), "");
+        assert_eq!(cst.to_source(), "");
+        for error in cst.errors() {
+            assert!(error.starts_with("test:1:1: "), "{:?}: {}", entry, error);
+        }
+    }
+    // Stored definition may be empty
+    assert!(parse("", EntryPoint::StoredDefinition).errors().is_empty());
+    assert!(!parse("", EntryPoint::Expression).errors().is_empty());
+}
+
+#[test]
+fn test_comment_only_input() {
+    let code = "// foo\n/* bar */ /* baz */\n\n\n// qux";
+    for entry in EntryPoint::ALL {
+        let cst = parse(code, *entry);
+        assert_eq!(cst.pretty_print(), "// foo\n/* bar */ /* baz */\n\n// qux\n");
+        assert_eq!(cst.to_source(), code);
+    }
+}
+
+#[test]
+fn test_trailing_comments() {
+    let cst = parse(
+        "model Foo end Foo; // foo\n\n\n/* bar */\n// baz\n",
+        EntryPoint::StoredDefinition,
+    );
+    assert_eq!(
+        cst.pretty_print(),
+        "model Foo\n\nend Foo; // foo\n\n/* bar */\n// baz\n"
+    );
+    let cst = parse("x + 1 // foo", EntryPoint::Expression);
+    assert_eq!(cst.pretty_print(), "x + 1 // foo\n");
+}
+
+#[test]
+fn test_leading_comments() {
+    let cst = parse(
+        "// foo\n\n\n/* bar */ model Foo end Foo;",
+        EntryPoint::StoredDefinition,
+    );
+    assert_eq!(
+        cst.pretty_print(),
+        "// foo\n\n/* bar */ model Foo\n\nend Foo;\n"
+    );
+    let cst = parse("// foo\nx := 1;", EntryPoint::Script);
+    assert_eq!(cst.pretty_print(), "// foo\nx := 1;\n");
+}
+
+#[test]
+fn test_truncated_input() {
+    for (entry, code) in common::SAMPLES {
+        let cst = parse(code, *entry);
+        let tokens = cst.tokens();
+        for tok in tokens.all() {
+            // Parser has to stop at the end of input instead of getting stuck
+            let cut = &code[..tokens.range(tok).start];
+            let truncated = parse(cut, *entry);
+            assert_eq!(truncated.to_source(), cut, "{:?}: {:?}", entry, cut);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::fs;
 // Helper functions
 fn format_file(path: &str) -> String {
     let input = fs::read_to_string(path).expect("error");
-    let parsed = mofmt::ModelicaCST::from(String::from(path), input, mofmt::SyntaxKind::StoredDefinition);
+    let parsed = mofmt::ModelicaCST::from(String::from(path), input, mofmt::EntryPoint::StoredDefinition);
     parsed.pretty_print()
 }
 
@@ -13,3 +13,169 @@ fn test_formatting() {
     let expected = fs::read_to_string("tests/samples/code-output.mo").expect("error");
     assert_eq!(expected, formatted);
 }
+
+#[test]
+fn test_script_formatting() {
+    let input = fs::read_to_string("tests/samples/script-input.mos").expect("error");
+    let parsed = mofmt::parse_script(&input, "script-input.mos").expect("error");
+    let expected = fs::read_to_string("tests/samples/script-output.mos").expect("error");
+    assert_eq!(expected, parsed.pretty_print());
+}
+
+#[cfg(feature = "optimica")]
+#[test]
+fn test_optimica_formatting() {
+    let formatted = format_file("tests/samples/optimica-input.mo");
+    let expected = fs::read_to_string("tests/samples/optimica-output.mo").expect("error");
+    assert_eq!(expected, formatted);
+}
+
+#[cfg(feature = "metamodelica")]
+#[test]
+fn test_metamodelica_formatting() {
+    let formatted = format_file("tests/samples/metamodelica-input.mo");
+    let expected = fs::read_to_string("tests/samples/metamodelica-output.mo").expect("error");
+    assert_eq!(expected, formatted);
+}
+
+#[test]
+fn test_format_str() {
+    let input = fs::read_to_string("tests/samples/code-input.mo").expect("error");
+    let expected = fs::read_to_string("tests/samples/code-output.mo").expect("error");
+    let options = mofmt::FormatOptions::new();
+    assert_eq!(mofmt::format_str(&input, &options), Ok(expected));
+    let error = mofmt::format_str("model Foo\n  Real x\nend Foo;", &options).unwrap_err();
+    assert!(matches!(error, mofmt::FormatError::Parse(_)));
+    assert!(error.to_string().contains("<input>:3:1:"));
+}
+
+#[test]
+fn test_html_formatting() {
+    let input = "model Foo\n  annotation (Documentation(info = \"<html><p>Foo\n  model</p></html>\", revisions = \"<p>x</p>\"), Text(textString = \"<html><p>x</p></html>\"));\nend Foo;\n";
+    let mut options = mofmt::FormatOptions::new();
+    let unchanged = mofmt::format_str(input, &options).unwrap();
+    assert!(unchanged.contains("\"<html><p>Foo\n  model</p></html>\""));
+    options.html = true;
+    let formatted = mofmt::format_str(input, &options).unwrap();
+    assert!(formatted.contains("info = \"<html>\n<p>Foo model</p>\n</html>\""));
+    assert!(formatted.contains("revisions = \"<p>x</p>\""));
+    assert!(formatted.contains("textString = \"<html><p>x</p></html>\""));
+    assert_eq!(mofmt::format_str(&formatted, &options), Ok(formatted));
+}
+
+#[test]
+fn test_matrix_alignment() {
+    let input = "model Foo\n  Real A[3, 3] = [1, 2.5, x;\n 30, -4, y;\n -1e3, foo(1, 2), zzz];\n  Real B[2, 2] = [a, bb; ccc, d];\n  Real C[2, 2] = [1, 22;\n (3 +\n 4), 5];\nend Foo;";
+    let mut options = mofmt::FormatOptions::new();
+    options.align_matrices = true;
+    let expected = "model Foo\n\n  Real A[3, 3] = [\n       1,       2.5, x;\n      30,        -4, y;\n    -1e3, foo(1, 2), zzz];\n  Real B[2, 2] = [a, bb; ccc, d];\n  Real C[2, 2] = [\n    1, 22;\n    (3\n      + 4),\n    5];\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    assert_eq!(mofmt::format_str(expected, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_blank_line_options() {
+    let input = "model Foo\n  Real x;\n\n\n\n  Real y;\n  model Bar\n  end Bar;\nprotected\n  Real z;\nequation\n  x = y;\n\n\n  // foo\n\n\n  y = z;\nend Foo;";
+    let mut options = mofmt::FormatOptions::new();
+    let expected = "model Foo\n\n  Real x;\n\n  Real y;\n  model Bar\n\n  end Bar;\n\nprotected\n\n  Real z;\n\nequation\n\n  x = y;\n\n  // foo\n\n  y = z;\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    options.max_blank_lines = 2;
+    options.blank_lines_around_sections = false;
+    options.blank_lines_in_composition = false;
+    options.blank_line_before_classes = true;
+    let expected = "model Foo\n  Real x;\n\n\n  Real y;\n\n  model Bar\n  end Bar;\nprotected\n  Real z;\nequation\n  x = y;\n\n\n  // foo\n\n\n  y = z;\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    assert_eq!(mofmt::format_str(expected, &options).unwrap(), expected);
+    options.max_blank_lines = 0;
+    let expected = "model Foo\n  Real x;\n  Real y;\n\n  model Bar\n  end Bar;\nprotected\n  Real z;\nequation\n  x = y;\n  // foo\n  y = z;\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_comment_normalization() {
+    let input = "model Foo\n      /* foo  \n       * bar\n    * baz\n       */\n  //foo   \n  Real x; //bar\n  // a long comment that should be wrapped\nend Foo;";
+    let mut options = mofmt::FormatOptions::new();
+    let formatted = mofmt::format_str(input, &options).unwrap();
+    assert!(formatted.contains("  /* foo\n       * bar\n    * baz\n       */\n  //foo\n"));
+    options.normalize_comments = true;
+    options.comment_width = Some(30);
+    let expected = "model Foo\n\n  /* foo\n   * bar\n* baz\n   */\n  // foo\n  Real x; // bar\n\n// a long comment that should\n// be wrapped\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    assert_eq!(mofmt::format_str(expected, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_comment_wrapping() {
+    let input = "model Foo\n  Real x;\n  //   a  = b  + c\n  ///////////////////\n  //-----------------\n  Real y;\nend Foo;";
+    let mut options = mofmt::FormatOptions::new();
+    options.comment_width = Some(15);
+    // Width has no effect without normalization
+    let formatted = mofmt::format_str(input, &options).unwrap();
+    assert!(formatted.contains("  //   a  = b  + c\n  ///////////////////\n  //-----------------\n"));
+    options.normalize_comments = true;
+    let expected = "model Foo\n\n  Real x;\n  //   a  = b\n  //   + c\n  ///////////////////\n  //-----------------\n  Real y;\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    assert_eq!(mofmt::format_str(expected, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_whitespace_and_bom() {
+    let input = "\u{feff}model Foo \"a  \n  b  \"  \n  /* c  \n  d */  \n  Real x;\t\nend Foo;\n\n\n";
+    let mut options = mofmt::FormatOptions::new();
+    let expected = "\u{feff}model Foo\n  \"a  \n  b  \"\n\n  /* c\n  d */\n  Real x;\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    let cst = mofmt::parse(input, "test").unwrap();
+    assert_eq!(cst.to_source(), input);
+    options.remove_bom = true;
+    assert_eq!(
+        mofmt::format_str(input, &options).unwrap(),
+        expected.trim_start_matches('\u{feff}')
+    );
+    // Input with only the byte order mark is formatted as empty
+    options.remove_bom = false;
+    assert_eq!(mofmt::format_str("\u{feff}", &options).unwrap(), "");
+    assert_eq!(mofmt::format_str("\u{feff}\n\n", &options).unwrap(), "");
+    // Byte order mark is only allowed at the beginning
+    assert!(mofmt::parse("model Foo\u{feff} end Foo;", "test").is_err());
+}
+
+#[test]
+fn test_number_normalization() {
+    let input = "model Foo\n  Real A[2, 2] = [1., 2E+3;\n 30, 4.5e-1];\n  Integer n = 10;\nend Foo;";
+    let mut options = mofmt::FormatOptions::new();
+    let formatted = mofmt::format_str(input, &options).unwrap();
+    assert!(formatted.contains("[\n    1., 2E+3;\n    30, 4.5e-1]"));
+    options.normalize_numbers = true;
+    options.align_matrices = true;
+    let expected = "model Foo\n\n  Real A[2, 2] = [\n    1.0,    2e3;\n     30, 4.5e-1];\n  Integer n = 10;\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+    assert_eq!(mofmt::format_str(expected, &options).unwrap(), expected);
+    // Columns are aligned using the normalized literals
+    let input = "model Foo\n  Real A[2, 2] = [1., -2E+3;\n 30, 4];\nend Foo;";
+    let expected = "model Foo\n\n  Real A[2, 2] = [\n    1.0, -2e3;\n     30,    4];\n\nend Foo;\n";
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_inline_comment_spacing() {
+    let input = "model Foo\n  Real x;// x\n  Real y;      // y\nprotected    // protected\n  Real z;\nend Foo;";
+    let expected = "model Foo\n\n  Real x; // x\n  Real y; // y\n\nprotected // protected\n\n  Real z;\n\nend Foo;\n";
+    let options = mofmt::FormatOptions::new();
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_external_clause() {
+    let input = "function Foo\nexternal\"C\" y = foo(x)\n  annotation(Library = \"foo\");\nend Foo;";
+    let expected = "function Foo\n\nexternal \"C\"\n\n  y = foo(x)\n    annotation (Library = \"foo\");\n\nend Foo;\n";
+    let options = mofmt::FormatOptions::new();
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_within_after_comments() {
+    let input = "// Header\n\n/* Note */ within Foo;\nmodel A\nend A;\n";
+    let expected = "// Header\n\n/* Note */\nwithin Foo;\nmodel A\n\nend A;\n";
+    let options = mofmt::FormatOptions::new();
+    assert_eq!(mofmt::format_str(input, &options).unwrap(), expected);
+}